@@ -0,0 +1,348 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::{
+    commands::video::{emit_failure, emit_segment_progress, CompressionSettings, ProcessingResult, VideoSegment},
+    error::Error,
+    jobs::{JobKind, JobManager, Task},
+    utils::{fill_reserved_output, unique_output_path},
+    video::{cutter, detector, merger},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use tauri::{command, AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
+
+/// Kept pieces shorter than this are dropped, they'd only add a glitch
+const MIN_PIECE_LENGTH: f64 = 0.05;
+
+/// A detected shot change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneCut {
+    pub time: f64,  // in seconds
+    pub score: f64, // 0-1, how different the frame is from the previous one
+}
+
+/// A span of the video in seconds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilenceSettings {
+    pub noise_db: f64,     // audio below this level counts as silence
+    pub min_duration: f64, // shortest silence in seconds
+    pub padding: f64,      // seconds of silence kept around speech
+}
+
+impl Default for SilenceSettings {
+    fn default() -> Self {
+        Self {
+            noise_db: -30.0,
+            min_duration: 0.5,
+            padding: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SceneDetection {
+    pub cuts: Vec<SceneCut>,
+    /// One segment per scene, ready to pass to the cut commands
+    pub segments: Vec<VideoSegment>,
+}
+
+/// Detect shot changes, emitting a `scene_detected` event for each cut as it
+/// is found. `threshold` is the scene score (0-1) a frame needs to count as a
+/// cut and `min_length` the shortest scene in seconds.
+#[command]
+pub async fn detect_scenes(
+    app: AppHandle,
+    path: String,
+    threshold: Option<f64>,
+    min_length: Option<f64>,
+) -> Result<SceneDetection, Error> {
+    let threshold = threshold.unwrap_or(0.4);
+    let min_length = min_length.unwrap_or(1.0).max(0.0);
+
+    let duration = cutter::get_metadata(&path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
+
+    let mut cuts = detector::detect_scenes(&path, threshold, min_length, |cut| {
+        let _ = app.emit(
+            "scene_detected",
+            json!({
+                "path": path,
+                "time": cut.time,
+                "score": cut.score,
+                "progress": (cut.time / duration * 100.0).clamp(0.0, 100.0).round() as u8
+            }),
+        );
+    })
+    .await
+    .map_err(|e| Error::new("Failed to detect scenes", e))?;
+
+    // A final scene shorter than `min_length` is folded into the one before
+    if cuts.last().is_some_and(|cut| duration - cut.time < min_length) {
+        cuts.pop();
+    }
+
+    let segments = scene_segments(&path, &cuts, duration);
+
+    Ok(SceneDetection { cuts, segments })
+}
+
+/// Segments between consecutive cuts, named `<file>_scene_001` and so on
+fn scene_segments(path: &str, cuts: &[SceneCut], duration: f64) -> Vec<VideoSegment> {
+    let stem = Path::new(path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    let boundaries: Vec<f64> = std::iter::once(0.0)
+        .chain(cuts.iter().map(|cut| cut.time))
+        .chain(std::iter::once(duration))
+        .collect();
+
+    boundaries
+        .windows(2)
+        .enumerate()
+        .map(|(i, range)| VideoSegment::new(range[0], range[1], format!("{}_scene_{:03}", stem, i + 1)))
+        .collect()
+}
+
+/// Find silent ranges in the first audio stream. `noise_db` defaults to -30 dB
+/// and `min_duration` to half a second.
+#[command]
+pub async fn detect_silence(
+    path: String,
+    noise_db: Option<f64>,
+    min_duration: Option<f64>,
+) -> Result<Vec<TimeRange>, Error> {
+    let defaults = SilenceSettings::default();
+
+    let duration = cutter::get_metadata(&path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
+
+    detector::detect_silence(
+        &path,
+        noise_db.unwrap_or(defaults.noise_db),
+        min_duration.unwrap_or(defaults.min_duration),
+        duration,
+        &CancellationToken::new(),
+        |_| {},
+    )
+    .await
+    .map_err(|e| Error::new("Failed to detect silence", e))
+}
+
+/// Find black sections lasting at least `min_duration` seconds (0.1 by
+/// default). `pixel_threshold` (0-1) is how dark a pixel must be.
+#[command]
+pub async fn detect_black_frames(
+    path: String,
+    min_duration: Option<f64>,
+    pixel_threshold: Option<f64>,
+) -> Result<Vec<TimeRange>, Error> {
+    let duration = cutter::get_metadata(&path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
+
+    detector::detect_black(
+        &path,
+        &TimeRange { start: 0.0, end: duration },
+        min_duration.unwrap_or(0.1),
+        pixel_threshold.unwrap_or(0.1),
+        &CancellationToken::new(),
+    )
+    .await
+    .map_err(|e| Error::new("Failed to detect black frames", e))
+}
+
+/// Find frozen sections lasting at least `min_duration` seconds (2 by
+/// default). Frames within `noise_db` (-60 dB) of each other are the same.
+#[command]
+pub async fn detect_frozen_frames(
+    path: String,
+    noise_db: Option<f64>,
+    min_duration: Option<f64>,
+) -> Result<Vec<TimeRange>, Error> {
+    let duration = cutter::get_metadata(&path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
+
+    detector::detect_freeze(
+        &path,
+        &TimeRange { start: 0.0, end: duration },
+        noise_db.unwrap_or(-60.0),
+        min_duration.unwrap_or(2.0),
+        &CancellationToken::new(),
+    )
+    .await
+    .map_err(|e| Error::new("Failed to detect frozen frames", e))
+}
+
+/// Queue a job that cuts out silent ranges and joins what's left into
+/// `<file>_no_silence.mp4`
+#[command]
+pub async fn remove_silence(
+    input_path: String,
+    output_dir: String,
+    settings: Option<SilenceSettings>,
+    compression_settings: Option<CompressionSettings>,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    Ok(jobs.enqueue(JobKind::RemoveSilence {
+        input_path,
+        output_dir,
+        settings: settings.unwrap_or_default(),
+        compression_settings: compression_settings.unwrap_or_default(),
+    }))
+}
+
+pub async fn remove_silence_task(
+    task: &Task,
+    input_path: &str,
+    output_dir: &str,
+    settings: &SilenceSettings,
+    compression_settings: &CompressionSettings,
+) -> ProcessingResult {
+    match remove_silence_internal(task, input_path, output_dir, settings, compression_settings).await {
+        Ok(output_path) => ProcessingResult::success(output_path),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to remove silence", e, None)
+        }
+    }
+}
+
+async fn remove_silence_internal(
+    task: &Task,
+    input_path: &str,
+    output_dir: &str,
+    settings: &SilenceSettings,
+    compression_settings: &CompressionSettings,
+) -> Result<String> {
+    let duration = cutter::get_metadata(input_path).await?.duration;
+
+    let silences = detector::detect_silence(
+        input_path,
+        settings.noise_db,
+        settings.min_duration,
+        duration,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "detecting silence", progress),
+    )
+    .await?;
+
+    let ranges = non_silent_ranges(&silences, duration, settings.padding.max(0.0));
+    if ranges.is_empty() {
+        return Err(Error::InvalidRequest {
+            message: "The video has no audio above the silence threshold".to_string(),
+        }
+        .into());
+    }
+
+    let stem = Path::new(input_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let output_path = unique_output_path(output_dir, &format!("{}_no_silence", stem))?;
+
+    let removal = merger::keep_ranges(input_path, &ranges, &output_path, compression_settings, &task.cancel, |progress| {
+        emit_segment_progress(task, "removing silence", progress)
+    });
+    fill_reserved_output(&output_path, removal).await?;
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// The ranges between `silences`, each widened by `padding` into the
+/// surrounding silence
+fn non_silent_ranges(silences: &[TimeRange], duration: f64, padding: f64) -> Vec<TimeRange> {
+    let mut ranges = Vec::new();
+    let mut start = 0.0;
+
+    for silence in silences {
+        let silence_start = silence.start + padding;
+        let silence_end = silence.end - padding;
+
+        // Too short to remove once the padding is kept
+        if silence_end <= silence_start {
+            continue;
+        }
+
+        if silence_start > start {
+            ranges.push(TimeRange { start, end: silence_start });
+        }
+        start = f64::max(start, silence_end);
+    }
+
+    if duration > start {
+        ranges.push(TimeRange { start, end: duration });
+    }
+
+    ranges.retain(|range| range.end - range.start >= MIN_PIECE_LENGTH);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silences(ranges: &[(f64, f64)]) -> Vec<TimeRange> {
+        ranges.iter().map(|&(start, end)| TimeRange { start, end }).collect()
+    }
+
+    fn kept(silences: &[TimeRange], duration: f64, padding: f64) -> Vec<(f64, f64)> {
+        non_silent_ranges(silences, duration, padding)
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_everything_without_silence() {
+        assert_eq!(kept(&[], 10.0, 0.5), [(0.0, 10.0)]);
+    }
+
+    #[test]
+    fn keeps_padding_around_each_silence() {
+        let silences = silences(&[(2.0, 5.0), (8.0, 10.0)]);
+
+        assert_eq!(kept(&silences, 10.0, 0.5), [(0.0, 2.5), (4.5, 8.5), (9.5, 10.0)]);
+    }
+
+    #[test]
+    fn skips_silences_shorter_than_the_padding() {
+        let silences = silences(&[(3.0, 3.8)]);
+
+        assert_eq!(kept(&silences, 10.0, 0.5), [(0.0, 10.0)]);
+    }
+
+    #[test]
+    fn drops_pieces_too_short_to_keep() {
+        let silences = silences(&[(0.0, 2.0)]);
+
+        assert_eq!(kept(&silences, 10.0, 0.03125), [(1.96875, 10.0)]);
+    }
+
+    #[test]
+    fn all_silent_leaves_nothing() {
+        let silences = silences(&[(0.0, 10.0)]);
+
+        assert!(kept(&silences, 10.0, 0.0).is_empty());
+    }
+}
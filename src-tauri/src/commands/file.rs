@@ -2,9 +2,10 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+use crate::server;
 use tauri::command;
-use tauri::{Runtime, Manager};
-use tauri_plugin_dialog::{Dialog, FileDialogBuilder, FilePath, DialogExt};
+use tauri::Runtime;
+use tauri_plugin_dialog::{FileDialogBuilder, FilePath, DialogExt};
 use tokio::sync::oneshot;
 
 #[command]
@@ -16,7 +17,7 @@ pub async fn select_file<R: Runtime>(window: tauri::Window<R>, filters: Option<V
     let mut builder = FileDialogBuilder::new(dialog);
 
     if let Some(filter_list) = filters {
-        for filter in filter_list {
+        for _filter in filter_list {
             builder = builder.add_filter("Video Files", &["mp4", "mov", "avi", "mkv"]);
         }
     } else {
@@ -28,7 +29,13 @@ pub async fn select_file<R: Runtime>(window: tauri::Window<R>, filters: Option<V
     });
 
     match rx.await {
-        Ok(selected_file) => Ok(selected_file),
+        Ok(selected_file) => {
+            // The user picked this file, so the player may stream it
+            if let Some(path) = &selected_file {
+                server::allow_file(path);
+            }
+            Ok(selected_file)
+        }
         Err(_) => Err("Failed to receive file selection".into()),
     }
 }
@@ -0,0 +1,42 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::error::Error;
+use crate::jobs::{JobManager, JobRecord};
+use tauri::{command, State};
+
+#[command]
+pub fn list_jobs(jobs: State<'_, JobManager>) -> Vec<JobRecord> {
+    jobs.list()
+}
+
+#[command]
+pub fn get_job(job_id: String, jobs: State<'_, JobManager>) -> Result<JobRecord, Error> {
+    jobs.get(&job_id)
+        .ok_or(Error::JobNotFound { job_id })
+}
+
+#[command]
+pub fn cancel_job(job_id: String, jobs: State<'_, JobManager>) -> Result<(), Error> {
+    if jobs.cancel(&job_id) {
+        Ok(())
+    } else {
+        Err(Error::JobNotFound { job_id })
+    }
+}
+
+#[command]
+pub fn resume_job(job_id: String, jobs: State<'_, JobManager>) -> Result<(), Error> {
+    jobs.resume(&job_id)
+}
+
+#[command]
+pub fn get_job_concurrency(jobs: State<'_, JobManager>) -> usize {
+    jobs.concurrency()
+}
+
+#[command]
+pub fn set_job_concurrency(limit: usize, jobs: State<'_, JobManager>) -> Result<(), Error> {
+    jobs.set_concurrency(limit)
+}
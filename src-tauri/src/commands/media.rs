@@ -0,0 +1,200 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::{
+    commands::video::{emit_failure, emit_segment_progress, ProcessingResult},
+    error::Error,
+    jobs::{JobKind, JobManager, Task},
+    video::{frames, proxy, sprites, waveform},
+};
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub height: u32,            // maximum height in pixels
+    pub keyframe_interval: u32, // frames between keyframes, 1 for all-intra
+    pub quality: u32,           // CRF, 0-51 (lower is better)
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            height: 540,
+            keyframe_interval: 12,
+            quality: 23,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpriteSettings {
+    pub interval: f64,                // seconds between thumbnails
+    pub scene_threshold: Option<f64>, // 0-1, sample on scene changes instead of the interval
+    pub width: u32,                   // thumbnail width in pixels
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl Default for SpriteSettings {
+    fn default() -> Self {
+        Self {
+            interval: 2.0,
+            scene_threshold: None,
+            width: 160,
+            columns: 10,
+            rows: 10,
+        }
+    }
+}
+
+/// Audio peaks for drawing a waveform
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Waveform {
+    pub start: f64,           // in seconds, the range the peaks cover
+    pub end: f64,             // in seconds
+    pub duration: f64,        // of the whole audio stream, in seconds
+    pub peaks: Vec<[f32; 2]>, // [min, max] per bucket, between -1 and 1
+}
+
+/// Image format for extracted frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    pub fn codec_args(self) -> [&'static str; 4] {
+        match self {
+            ImageFormat::Jpeg => ["-c:v", "mjpeg", "-q:v", "3"],
+            ImageFormat::Png => ["-c:v", "png", "-compression_level", "3"],
+            ImageFormat::Webp => ["-c:v", "libwebp", "-quality", "80"],
+        }
+    }
+}
+
+/// Save the frame at `time` seconds as an image and return its path.
+/// Frames are cached, so asking again for the same frame is cheap.
+#[command]
+pub async fn extract_frame(
+    path: String,
+    time: f64,
+    width: Option<u32>,
+    format: Option<ImageFormat>,
+) -> Result<String, Error> {
+    match frames::extract_frame(&path, time, width, format.unwrap_or_default()).await {
+        Ok(frame_path) => Ok(frame_path.to_string_lossy().to_string()),
+        Err(e) => Err(Error::new("Failed to extract frame", e)),
+    }
+}
+
+/// Min/max peak pairs for an audio stream, `buckets` of them spread over the
+/// whole file or over `start`..`end` seconds when zoomed in. The first call
+/// decodes the audio, later calls at any zoom level reuse the cached peaks.
+#[command]
+pub async fn get_waveform(
+    path: String,
+    stream_index: Option<u32>,
+    buckets: usize,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> Result<Waveform, Error> {
+    match waveform::get_waveform(&path, stream_index, buckets, start, end).await {
+        Ok(waveform) => Ok(waveform),
+        Err(e) => Err(Error::new("Failed to get waveform", e)),
+    }
+}
+
+/// Queue a low-resolution proxy of `path` for smooth playback. Once it exists
+/// the video server plays the proxy while cuts still use the original.
+#[command]
+pub async fn generate_proxy(
+    path: String,
+    settings: Option<ProxySettings>,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    Ok(jobs.enqueue(JobKind::Proxy {
+        source_path: path,
+        settings: settings.unwrap_or_default(),
+    }))
+}
+
+/// Path of the finished proxy for `path`, if there is one
+#[command]
+pub fn get_proxy(path: String) -> Option<String> {
+    proxy::existing_proxy(&path).map(|proxy| proxy.to_string_lossy().to_string())
+}
+
+pub async fn proxy_task(task: &Task, source_path: &str, settings: &ProxySettings) -> ProcessingResult {
+    let result = proxy::generate_proxy(
+        source_path,
+        settings,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "generating proxy", progress),
+    )
+    .await;
+
+    match result {
+        Ok(proxy_path) => ProcessingResult::success(proxy_path),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to generate proxy", e, None)
+        }
+    }
+}
+
+/// Queue sprite sheets and a WebVTT thumbnails index for timeline hover
+/// previews. Returns `None` when they are already cached.
+#[command]
+pub async fn generate_thumbnails(
+    path: String,
+    settings: Option<SpriteSettings>,
+    jobs: State<'_, JobManager>,
+) -> Result<Option<String>, Error> {
+    Ok(queue_thumbnails(&jobs, path, settings.unwrap_or_default()))
+}
+
+/// Queue thumbnails for `path` unless they are already cached
+pub fn queue_thumbnails(jobs: &JobManager, path: String, settings: SpriteSettings) -> Option<String> {
+    if sprites::has_sprites(&path) {
+        return None;
+    }
+
+    Some(jobs.enqueue(JobKind::Thumbnails {
+        source_path: path,
+        settings,
+    }))
+}
+
+pub async fn thumbnails_task(task: &Task, source_path: &str, settings: &SpriteSettings) -> ProcessingResult {
+    let result = sprites::generate_sprites(
+        source_path,
+        settings,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "generating thumbnails", progress),
+    )
+    .await;
+
+    match result {
+        Ok(vtt_path) => ProcessingResult::success(vtt_path),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to generate thumbnails", e, None)
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod analysis;
 pub mod file;
+pub mod jobs;
+pub mod media;
+pub mod timeline;
 pub mod video;
@@ -0,0 +1,175 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::{
+    commands::video::{emit_failure, emit_segment_progress, CompressionSettings, ProcessingResult, Transition, VideoSegment},
+    error::Error,
+    jobs::{JobKind, JobManager, Task},
+    utils::{fill_reserved_output, unique_output_path},
+    video::{cutter, merger, process::Progress, validator},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{command, State};
+
+/// A segment of `source_path` placed on the timeline
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimelineClip {
+    pub source_path: String,
+    #[serde(flatten)]
+    pub segment: VideoSegment,
+}
+
+/// Queue a job that cuts every clip and joins them in order into
+/// `<output_dir>/<output_name>.mp4`, with `transition` between clips
+#[command]
+pub async fn render_timeline(
+    clips: Vec<TimelineClip>,
+    output_dir: String,
+    output_name: String,
+    compression_settings: Option<CompressionSettings>,
+    transition: Option<Transition>,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    if let Some(reason) = validator::invalid_name_reason(&output_name) {
+        return Err(Error::InvalidRequest { message: reason });
+    }
+
+    let clips = checked_clips(clips).await?;
+
+    Ok(jobs.enqueue(JobKind::RenderTimeline {
+        clips,
+        output_dir,
+        output_name,
+        compression_settings: compression_settings.unwrap_or_default(),
+        transition: transition.unwrap_or_default(),
+    }))
+}
+
+/// Validate each clip against its own source, like `checked_segments`
+async fn checked_clips(mut clips: Vec<TimelineClip>) -> Result<Vec<TimelineClip>, Error> {
+    if clips.is_empty() {
+        return Err(Error::InvalidRequest {
+            message: "The timeline has no clips".to_string(),
+        });
+    }
+
+    let mut durations: HashMap<String, f64> = HashMap::new();
+    let mut diagnostics = Vec::with_capacity(clips.len());
+
+    for (index, clip) in clips.iter().enumerate() {
+        let duration = match durations.get(&clip.source_path) {
+            Some(&duration) => duration,
+            None => {
+                let duration = cutter::get_metadata(&clip.source_path)
+                    .await
+                    .map_err(|e| Error::new("Failed to read video", e))?
+                    .duration;
+                durations.insert(clip.source_path.clone(), duration);
+                duration
+            }
+        };
+
+        let mut diagnostic = validator::validate(std::slice::from_ref(&clip.segment), duration).remove(0);
+        diagnostic.index = index;
+        diagnostics.push(diagnostic);
+    }
+
+    let invalid = diagnostics.iter().filter(|d| !d.is_valid()).count();
+
+    if invalid > 0 {
+        return Err(Error::InvalidSegment {
+            message: format!("{} of {} clips can't be processed", invalid, clips.len()),
+            diagnostics,
+        });
+    }
+
+    for (clip, diagnostic) in clips.iter_mut().zip(&diagnostics) {
+        clip.segment.clamp_to(diagnostic);
+    }
+
+    Ok(clips)
+}
+
+pub async fn render_timeline_task(
+    task: &Task,
+    clips: &[TimelineClip],
+    output_dir: &str,
+    output_name: &str,
+    compression_settings: &CompressionSettings,
+    transition: &Transition,
+) -> ProcessingResult {
+    let result = async {
+        let output_path = unique_output_path(output_dir, output_name)?;
+        let render = render_clips(task, clips, &output_path, compression_settings, transition);
+        fill_reserved_output(&output_path, render).await?;
+        Ok::<_, anyhow::Error>(output_path)
+    }
+    .await;
+
+    match result {
+        Ok(output_path) => ProcessingResult::success(output_path.to_string_lossy().to_string()),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to render timeline", e, None)
+        }
+    }
+}
+
+/// Cut `clips` into a folder next to `output_path` and join them into it
+async fn render_clips(
+    task: &Task,
+    clips: &[TimelineClip],
+    output_path: &Path,
+    compression_settings: &CompressionSettings,
+    transition: &Transition,
+) -> Result<()> {
+    // The output name is reserved, so a folder named after it is unique, and
+    // the pieces land on the disk chosen for the output
+    let pieces_dir = output_path.with_extension("pieces");
+    std::fs::create_dir_all(&pieces_dir)?;
+
+    let result = cut_and_join(task, clips, &pieces_dir, output_path, compression_settings, transition).await;
+    let _ = std::fs::remove_dir_all(&pieces_dir);
+    result
+}
+
+async fn cut_and_join(
+    task: &Task,
+    clips: &[TimelineClip],
+    pieces_dir: &Path,
+    output_path: &Path,
+    compression_settings: &CompressionSettings,
+    transition: &Transition,
+) -> Result<()> {
+    let pieces_dir = pieces_dir.to_string_lossy();
+    let mut pieces = Vec::with_capacity(clips.len());
+
+    for (i, clip) in clips.iter().enumerate() {
+        let cut = cutter::cut_segment(
+            &clip.source_path,
+            &clip.segment,
+            &pieces_dir,
+            compression_settings,
+            &task.cancel,
+            |progress| {
+                let overall = Progress {
+                    percent: (i as f64 * 100.0 + progress.percent) / clips.len() as f64,
+                    ..progress.clone()
+                };
+                emit_segment_progress(task, "cutting", &overall);
+            },
+        )
+        .await?;
+
+        pieces.push(cut.path);
+    }
+
+    merger::join_clips(&pieces, output_path, compression_settings, transition, &task.cancel, |progress| {
+        emit_segment_progress(task, "joining", progress)
+    })
+    .await
+}
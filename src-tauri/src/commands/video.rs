@@ -3,22 +3,129 @@
 // LICENSE file in the root directory of this source tree.
 
 use crate::{
-    utils::get_ffmpeg_path,
-    video::{cutter, encoder, merger},
+    commands::media::{self, SpriteSettings},
+    error::Error,
+    jobs::{JobKind, JobManager, Task},
+    server,
+    video::{
+        cutter, encoder, merger,
+        process::Progress,
+        validator::{self, SegmentDiagnostic},
+    },
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{path::{Path, PathBuf}, process::Stdio};
-use tauri::{command, Emitter, Window};
-use tokio::process::Command;
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::{command, Emitter, State};
 use tokio::time::Instant;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How a segment is cut out of the source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CutMode {
+    /// Stream copy, fast but snaps to keyframes
+    #[default]
+    Copy,
+    /// Full re-encode with exact boundaries
+    Accurate,
+    /// Re-encode only the partial GOPs at both ends, copy the rest
+    Smart,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VideoSegment {
     start_time: f64, // in seconds
     end_time: f64,   // in seconds
     intro_path: Option<String>,
     output_name: String,
+    #[serde(default)]
+    cut_mode: CutMode,
+    /// Clips played before the segment, after `intro_path`
+    #[serde(default)]
+    pre_roll: Vec<String>,
+    /// Clips played after the segment, e.g. an outro and an end card
+    #[serde(default)]
+    post_roll: Vec<String>,
+    #[serde(default)]
+    fade_in: Fade,
+    #[serde(default)]
+    fade_out: Fade,
+    /// Transition between the bumpers and the segment
+    #[serde(default)]
+    transition: Transition,
+    /// Tighten the range to skip black or frozen frames at either end
+    #[serde(default)]
+    trim_black_edges: bool,
+}
+
+impl VideoSegment {
+    /// A plain stream-copy segment without intro
+    pub fn new(start_time: f64, end_time: f64, output_name: String) -> Self {
+        Self {
+            start_time,
+            end_time,
+            intro_path: None,
+            output_name,
+            cut_mode: CutMode::default(),
+            pre_roll: Vec::new(),
+            post_roll: Vec::new(),
+            fade_in: Fade::default(),
+            fade_out: Fade::default(),
+            transition: Transition::default(),
+            trim_black_edges: false,
+        }
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.end_time
+    }
+
+    pub fn output_name(&self) -> &str {
+        &self.output_name
+    }
+
+    pub fn cut_mode(&self) -> CutMode {
+        self.cut_mode
+    }
+
+    pub fn fade_in(&self) -> &Fade {
+        &self.fade_in
+    }
+
+    pub fn fade_out(&self) -> &Fade {
+        &self.fade_out
+    }
+
+    pub fn transition(&self) -> &Transition {
+        &self.transition
+    }
+
+    pub fn trim_black_edges(&self) -> bool {
+        self.trim_black_edges
+    }
+
+    /// The intro and pre-roll clips, `video_path` and the post-roll clips,
+    /// in playing order
+    pub fn playlist(&self, video_path: &str) -> Vec<String> {
+        self.intro_path
+            .iter()
+            .chain(&self.pre_roll)
+            .cloned()
+            .chain(std::iter::once(video_path.to_string()))
+            .chain(self.post_roll.iter().cloned())
+            .collect()
+    }
+
+    /// Use the range the validator clamped the segment to
+    pub fn clamp_to(&mut self, diagnostic: &SegmentDiagnostic) {
+        self.start_time = diagnostic.start_time;
+        self.end_time = diagnostic.end_time;
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +135,46 @@ pub struct VideoMetadata {
     pub height: u32,
     pub framerate: f64,
     pub codec: String,
+    pub format: String,                   // container, e.g. "mov,mp4,m4a,3gp,3g2,mj2"
+    pub bit_rate: Option<u64>,            // overall, in bits/s
+    pub video_bit_rate: Option<u64>,      // in bits/s
+    pub pixel_format: Option<String>,     // e.g. "yuv420p", "yuv420p10le"
+    pub color_space: Option<String>,      // e.g. "bt709", "bt2020nc"
+    pub color_transfer: Option<String>,   // e.g. "smpte2084" (PQ) or "arib-std-b67" (HLG)
+    pub color_primaries: Option<String>,
+    pub rotation: i32,                    // in degrees, as the player should display it
+    pub sample_aspect_ratio: Option<String>,
+    pub variable_frame_rate: bool,
+    pub audio_streams: Vec<AudioStream>,
+    pub subtitle_streams: Vec<SubtitleStream>,
+    pub chapters: Vec<Chapter>,
+    pub tags: HashMap<String, String>,    // container tags such as title or creation_time
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AudioStream {
+    pub index: u32,
+    pub codec: String,
+    pub channels: u32,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtitleStream {
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start: f64, // in seconds
+    pub end: f64,   // in seconds
+    pub title: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)] // Enable serialization & deserialization
@@ -35,9 +182,124 @@ pub struct CompressionSettings {
     quality: u32,   // 0-51 for x264/x265 (lower is better)
     preset: String, // e.g., "medium", "slow", "veryslow"
     codec: String,  // e.g., "libx264", "libx265"
+    #[serde(default)]
+    overlay: Option<OverlaySettings>, // watermark burned in while compressing
+}
+
+/// Where an overlay sits in the frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+/// An image such as a logo drawn over the video
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    pub image_path: String,
+    pub anchor: Anchor,
+    pub margin_x: u32,      // in pixels from the anchored edge, ignored for center
+    pub margin_y: u32,
+    pub scale: f64,         // overlay width as a fraction of the video width
+    pub opacity: f64,       // 0-1
+    pub start: Option<f64>, // in seconds, shown from the start if unset
+    pub end: Option<f64>,   // in seconds, shown until the end if unset
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            image_path: String::new(),
+            anchor: Anchor::default(),
+            margin_x: 24,
+            margin_y: 24,
+            scale: 0.15,
+            opacity: 1.0,
+            start: None,
+            end: None,
+        }
+    }
+}
+
+/// Fade lengths in seconds, 0 for none
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fade {
+    pub video: f64, // from or to black
+    pub audio: f64, // from or to silence
+}
+
+impl Fade {
+    pub fn is_none(&self) -> bool {
+        self.video <= 0.0 && self.audio <= 0.0
+    }
+}
+
+/// How one clip turns into the next when clips are joined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    /// Hard cut
+    #[default]
+    None,
+    Crossfade,
+    /// Fade out to black, then in from black
+    FadeBlack,
+    FadeWhite,
+    Wipe,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration: f64, // in seconds, the clips overlap by this much
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::None,
+            duration: 1.0,
+        }
+    }
+}
+
+impl Transition {
+    /// Whether clips are simply played one after the other
+    pub fn is_cut(&self) -> bool {
+        self.kind == TransitionKind::None || self.duration <= 0.0
+    }
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            quality: 23,
+            preset: "medium".to_string(),
+            codec: "libx264".to_string(),
+            overlay: None,
+        }
+    }
 }
 
 impl CompressionSettings {
+    /// Settings for re-encoding merged clips when none are given
+    pub fn for_merging() -> Self {
+        Self {
+            quality: 28,
+            preset: "fast".to_string(),
+            codec: "libx264".to_string(),
+            overlay: None,
+        }
+    }
+
     pub fn quality(&self) -> u32 {
         self.quality
     }
@@ -49,84 +311,174 @@ impl CompressionSettings {
     pub fn codec(&self) -> &str {
         &self.codec
     }
+
+    pub fn overlay(&self) -> Option<&OverlaySettings> {
+        self.overlay.as_ref()
+    }
+
+    /// Fail if the overlay image is missing, checked before any work is done
+    /// as compression is the last step of a job
+    pub fn check_overlay(&self) -> Result<(), Error> {
+        match &self.overlay {
+            Some(overlay) if !Path::new(&overlay.image_path).is_file() => Err(Error::InvalidRequest {
+                message: format!("Overlay image {} not found", overlay.image_path),
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProcessingResult {
     success: bool,
     output_path: Option<String>,
     error_message: Option<String>,
+    error: Option<Error>,      // structured form of `error_message`
+    actual_start: Option<f64>, // where the cut really started in the source, in seconds
+    actual_end: Option<f64>,   // where the cut really ended in the source, in seconds
+}
+
+impl ProcessingResult {
+    pub fn success(output_path: String) -> Self {
+        Self {
+            success: true,
+            output_path: Some(output_path),
+            error_message: None,
+            error: None,
+            actual_start: None,
+            actual_end: None,
+        }
+    }
+
+    /// Record the source range a cut actually covered
+    fn with_cut_range(mut self, cut: &cutter::CutOutput) -> Self {
+        self.actual_start = Some(cut.actual_start);
+        self.actual_end = Some(cut.actual_end);
+        self
+    }
+
+    /// Result for a task that was cancelled before it started
+    pub fn cancelled() -> Self {
+        Self::error(Error::Cancelled, None)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    /// Result for a failed step. `partial_output` is the file produced by the
+    /// previous step, which is discarded when the job was cancelled.
+    pub fn failure(context: &str, error: anyhow::Error, partial_output: Option<String>) -> Self {
+        let error = Error::new(context, error);
+
+        if matches!(error, Error::Cancelled) {
+            if let Some(path) = &partial_output {
+                let _ = std::fs::remove_file(path);
+            }
+
+            return Self::error(error, None);
+        }
+
+        Self::error(error, partial_output)
+    }
+
+    fn error(error: Error, output_path: Option<String>) -> Self {
+        Self {
+            success: false,
+            output_path,
+            error_message: Some(error.to_string()),
+            error: Some(error),
+            actual_start: None,
+            actual_end: None,
+        }
+    }
 }
 
 #[command]
-pub async fn load_video(path: String) -> Result<VideoMetadata, String> {
-    match cutter::get_metadata(&path) {
-        Ok(metadata) => Ok(metadata),
-        Err(e) => Err(format!("Failed to load video: {}", e)),
+pub async fn load_video(path: String, jobs: State<'_, JobManager>) -> Result<VideoMetadata, Error> {
+    match cutter::get_metadata(&path).await {
+        Ok(metadata) => {
+            // Let the player stream the file it just opened
+            server::allow_file(&path);
+            // Timeline hover previews are built in the background
+            media::queue_thumbnails(&jobs, path, SpriteSettings::default());
+            Ok(metadata)
+        }
+        Err(e) => Err(Error::new("Failed to load video", e)),
     }
 }
 
 #[command]
-pub async fn get_video_metadata(path: String) -> Result<VideoMetadata, String> {
-    match cutter::get_metadata(&path) {
+pub async fn get_video_metadata(path: String) -> Result<VideoMetadata, Error> {
+    match cutter::get_metadata(&path).await {
         Ok(metadata) => Ok(metadata),
-        Err(e) => Err(format!("Failed to get video metadata: {}", e)),
+        Err(e) => Err(Error::new("Failed to get video metadata", e)),
     }
 }
 
+/// Keyframe timestamps of a video, for snapping segment boundaries
 #[command]
-pub async fn cut_video(
+pub async fn get_keyframes(path: String) -> Result<Vec<f64>, Error> {
+    match cutter::keyframe_index(&path).await {
+        Ok(keyframes) => Ok(keyframes.to_vec()),
+        Err(e) => Err(Error::new("Failed to read keyframes", e)),
+    }
+}
+
+/// Check segments against the video without starting any work
+#[command]
+pub async fn validate_segments(
     input_path: String,
     segments: Vec<VideoSegment>,
-    output_dir: String,
-) -> Vec<ProcessingResult> {
-    let mut results = Vec::new();
-
-    for segment in segments {
-        let result = match cutter::cut_segment(
-            &input_path,
-            segment.start_time,
-            segment.end_time,
-            &output_dir,
-            &segment.output_name,
-        ) {
-            Ok(output_path) => {
-                let mut final_path = output_path;
-
-                // Add intro if provided
-                if let Some(intro_path) = segment.intro_path {
-                    match merger::add_intro(&intro_path, &final_path, &output_dir) {
-                        Ok(merged_path) => {
-                            final_path = merged_path;
-                        }
-                        Err(e) => {
-                            results.push(ProcessingResult {
-                                success: false,
-                                output_path: Some(final_path),
-                                error_message: Some(format!("Failed to add intro: {}", e)),
-                            });
-                            continue;
-                        }
-                    }
-                }
+) -> Result<Vec<SegmentDiagnostic>, Error> {
+    let duration = cutter::get_metadata(&input_path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
 
-                ProcessingResult {
-                    success: true,
-                    output_path: Some(final_path),
-                    error_message: None,
-                }
-            }
-            Err(e) => ProcessingResult {
-                success: false,
-                output_path: None,
-                error_message: Some(format!("Failed to cut segment: {}", e)),
-            },
-        };
+    Ok(validator::validate(&segments, duration))
+}
+
+/// Validate segments before queueing a job, returning them clamped to the
+/// video or an error listing every segment's diagnostics
+async fn checked_segments(input_path: &str, mut segments: Vec<VideoSegment>) -> Result<Vec<VideoSegment>, Error> {
+    let duration = cutter::get_metadata(input_path)
+        .await
+        .map_err(|e| Error::new("Failed to read video", e))?
+        .duration;
+
+    let diagnostics = validator::validate(&segments, duration);
+    let invalid = diagnostics.iter().filter(|d| !d.is_valid()).count();
 
-        results.push(result);
+    if invalid > 0 {
+        return Err(Error::InvalidSegment {
+            message: format!("{} of {} segments can't be processed", invalid, segments.len()),
+            diagnostics,
+        });
+    }
+
+    for (segment, diagnostic) in segments.iter_mut().zip(&diagnostics) {
+        segment.clamp_to(diagnostic);
     }
 
-    results
+    Ok(segments)
+}
+
+#[command]
+pub async fn cut_video(
+    input_path: String,
+    segments: Vec<VideoSegment>,
+    output_dir: String,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    let segments = checked_segments(&input_path, segments).await?;
+
+    Ok(jobs.enqueue(JobKind::Cut {
+        input_path,
+        segments,
+        output_dir,
+        with_progress: false,
+    }))
 }
 
 #[command]
@@ -134,292 +486,237 @@ pub async fn cut_video_with_progress(
     input_path: String,
     segments: Vec<VideoSegment>,
     output_dir: String,
-    window: Window,
-) -> Vec<ProcessingResult> {
-    let mut results = Vec::new();
-
-    for (index, segment) in segments.iter().enumerate() {
-        let segment_start_time = Instant::now();
-
-        // Emit initial progress event
-        let _ = window.emit(
-            "segment_progress",
-            json!({
-                "index": index,
-                "total": segments.len(),
-                "status": "cutting",
-                "progress": 0,
-                "estimated_time": null
-            }),
-        );
-
-        // Start cutting process
-        let result = match cutter::cut_segment(
-            &input_path,
-            segment.start_time,
-            segment.end_time,
-            &output_dir,
-            &segment.output_name,
-        ) {
-            Ok(output_path) => {
-                let mut final_path = output_path;
-
-                // Emit 50% progress after cutting
-                let _ = window.emit(
-                    "segment_progress",
-                    json!({
-                        "index": index,
-                        "total": segments.len(),
-                        "status": "cutting",
-                        "progress": 50
-                    }),
-                );
-
-                // Check if an intro needs to be added
-                if let Some(intro_path) = &segment.intro_path {
-                    // Emit event before intro processing
-                    let _ = window.emit(
-                        "segment_progress",
-                        json!({
-                            "index": index,
-                            "total": segments.len(),
-                            "status": "adding intro",
-                            "progress": 0,
-                            "estimated_time": null
-                        }),
-                    );
-
-                    match merger::add_intro_with_progress(
-                        intro_path.clone(),
-                        final_path.clone(),
-                        output_dir.clone(),
-                        None, // Compression settings (if needed)
-                        window.clone(),
-                    )
-                    .await
-                    {
-                        Ok(merged_path) => {
-                            final_path = merged_path;
-                        }
-                        Err(e) => {
-                            results.push(ProcessingResult {
-                                success: false,
-                                output_path: Some(final_path.clone()),
-                                error_message: Some(format!("Failed to add intro: {}", e)),
-                            });
-                            continue;
-                        }
-                    }
-                }
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    let segments = checked_segments(&input_path, segments).await?;
+
+    Ok(jobs.enqueue(JobKind::Cut {
+        input_path,
+        segments,
+        output_dir,
+        with_progress: true,
+    }))
+}
 
-                let elapsed_time = segment_start_time.elapsed();
-                let estimated_time =
-                    elapsed_time.as_secs_f64() * ((segments.len() - (index + 1)) as f64);
-
-                // Emit final success event
-                let _ = window.emit(
-                    "segment_progress",
-                    json!({
-                        "index": index,
-                        "total": segments.len(),
-                        "status": "completed",
-                        "progress": 100,
-                        "estimated_time": estimated_time
-                    }),
-                );
-
-                ProcessingResult {
-                    success: true,
-                    output_path: Some(final_path),
-                    error_message: None,
+/// Cut one segment and add its intro and other bumpers, if any
+pub async fn cut_segment_task(
+    task: &Task,
+    input_path: &str,
+    segment: &VideoSegment,
+    output_dir: &str,
+    with_progress: bool,
+) -> ProcessingResult {
+    let segment_start_time = Instant::now();
+
+    // Emit initial progress event
+    emit_progress(task, "cutting", 0, None);
+
+    // Start cutting process
+    let cut = match cutter::cut_segment(
+        input_path,
+        segment,
+        output_dir,
+        &CompressionSettings::default(),
+        &task.cancel,
+        |progress| emit_segment_progress(task, "cutting", progress),
+    )
+    .await
+    {
+        Ok(cut) => cut,
+        Err(e) => {
+            // Emit failure event
+            emit_failure(task);
+            return ProcessingResult::failure("Failed to cut segment", e, None);
+        }
+    };
+    let mut final_path = cut.path.clone();
+
+    // Check if bumpers need to be added
+    let playlist = segment.playlist(&final_path);
+    if playlist.len() > 1 {
+        // Emit event before merging
+        emit_progress(task, "adding bumpers", 0, None);
+
+        let merge_result = merger::merge_clips(
+            &playlist,
+            output_dir,
+            None, // Compression settings (if needed)
+            segment.transition(),
+            &task.cancel,
+            |progress| {
+                if with_progress {
+                    emit_segment_progress(task, "adding bumpers", progress);
                 }
+            },
+        )
+        .await;
+
+        match merge_result {
+            Ok(merged_path) => {
+                final_path = merged_path;
             }
             Err(e) => {
-                // Emit failure event
-                let _ = window.emit(
-                    "segment_progress",
-                    json!({
-                        "index": index,
-                        "total": segments.len(),
-                        "status": "failed",
-                        "progress": 0,
-                        "estimated_time": null
-                    }),
-                );
-
-                ProcessingResult {
-                    success: false,
-                    output_path: None,
-                    error_message: Some(format!("Failed to cut segment: {}", e)),
-                }
+                emit_failure(task);
+                return ProcessingResult::failure("Failed to add bumpers", e, Some(final_path));
             }
-        };
-
-        results.push(result);
+        }
     }
 
-    results
+    let elapsed_time = segment_start_time.elapsed();
+    let estimated_time = elapsed_time.as_secs_f64() * ((task.total - (task.index + 1)) as f64);
+
+    // Emit final success event
+    emit_progress(task, "completed", 100, Some(estimated_time));
+
+    ProcessingResult::success(final_path).with_cut_range(&cut)
 }
 
-fn emit_progress(
-    window: &Window,
-    index: usize,
-    total: usize,
-    status: &str,
-    progress: u8,
-    estimated_time: Option<f64>,
-) {
-    let _ = window.emit(
+fn emit_progress(task: &Task, status: &str, progress: u8, estimated_time: Option<f64>) {
+    let progress = Progress {
+        percent: progress.into(),
+        eta: estimated_time,
+        ..Default::default()
+    };
+    emit_segment_progress(task, status, &progress);
+}
+
+/// Report a segment that was cancelled before it started
+pub fn emit_cancelled(task: &Task) {
+    emit_progress(task, "cancelled", 0, None);
+}
+
+/// Emit a `segment_progress` event for the step named by `status`
+pub fn emit_segment_progress(task: &Task, status: &str, progress: &Progress) {
+    let _ = task.app.emit(
         "segment_progress",
         json!({
-            "index": index,
-            "total": total,
+            "job_id": task.job_id,
+            "index": task.index,
+            "total": task.total,
             "status": status,
-            "progress": progress,
-            "estimated_time": estimated_time
+            "progress": progress.percent.round() as u8,
+            "estimated_time": progress.eta,
+            "fps": progress.fps,
+            "speed": progress.speed
         }),
     );
 }
 
+/// Report a failed segment, or a cancelled one if the job was cancelled
+pub fn emit_failure(task: &Task) {
+    let status = if task.cancel.is_cancelled() { "cancelled" } else { "failed" };
+    emit_progress(task, status, 0, None);
+}
+
 #[command]
 pub async fn process_video_with_progress(
     input_path: String,
     segments: Vec<VideoSegment>,
     output_dir: String,
     compression_settings: CompressionSettings,
-    window: Window,
-) -> Vec<ProcessingResult> {
-    let mut results = Vec::new();
-
-    for (index, segment) in segments.iter().enumerate() {
-        let segment_start_time = Instant::now();
-
-        // Emit progress: Cutting started
-        let _ = window.emit(
-            "segment_progress",
-            json!({
-                "index": index,
-                "total": segments.len(),
-                "status": "cutting",
-                "progress": 0,
-            }),
-        );
-
-        // Step 1: Cut Video Segment
-        let segment_filename = format!("{}", segment.output_name);
-        let cut_result = cutter::cut_segment(
-            &input_path,
-            segment.start_time,
-            segment.end_time,
-            &output_dir,
-            &segment_filename,
-        );
-
-        let mut final_path = match cut_result {
-            Ok(output_path) => output_path,
-            Err(e) => {
-                results.push(ProcessingResult {
-                    success: false,
-                    output_path: None,
-                    error_message: Some(format!("Failed to cut segment: {}", e)),
-                });
-                continue;
-            }
-        };
-
-        // Emit progress: Cutting done
-        let _ = window.emit(
-            "segment_progress",
-            json!({
-                "index": index,
-                "total": segments.len(),
-                "status": "cutting",
-                "progress": 50,
-            }),
-        );
-
-        // Step 2: Add Intro (if available)
-        if let Some(intro_path) = &segment.intro_path {
-            let intro_filename = format!("{}_with_intro.mp4", segment.output_name);
-
-            let intro_result = merger::add_intro_with_progress(
-                intro_path.to_string(),
-                final_path.to_string(),
-                output_dir.to_string(),
-                None,
-                window.clone(),
-            )
-            .await;
-
-            match intro_result {
-                Ok(merged_path) => {
-                    // Remove the unmerged cut segment after merging
-                    let _ = std::fs::remove_file(&final_path);
-                    final_path = merged_path;
-                }
-                Err(e) => {
-                    results.push(ProcessingResult {
-                        success: false,
-                        output_path: Some(final_path.clone()),
-                        error_message: Some(format!("Failed to add intro: {}", e)),
-                    });
-                    continue;
-                }
-            }
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    compression_settings.check_overlay()?;
+    let segments = checked_segments(&input_path, segments).await?;
+
+    Ok(jobs.enqueue(JobKind::Process {
+        input_path,
+        segments,
+        output_dir,
+        compression_settings,
+    }))
+}
 
-            // Emit progress: Intro added
-            let _ = window.emit(
-                "segment_progress",
-                json!({
-                    "index": index,
-                    "total": segments.len(),
-                    "status": "adding intro",
-                    "progress": 100,
-                }),
-            );
+/// Cut, add bumpers and compress one segment
+pub async fn process_segment_task(
+    task: &Task,
+    input_path: &str,
+    segment: &VideoSegment,
+    output_dir: &str,
+    compression_settings: &CompressionSettings,
+) -> ProcessingResult {
+    let segment_start_time = Instant::now();
+
+    // Emit progress: Cutting started
+    emit_progress(task, "cutting", 0, None);
+
+    // Step 1: Cut Video Segment
+    let cut_result = cutter::cut_segment(
+        input_path,
+        segment,
+        output_dir,
+        compression_settings,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "cutting", progress),
+    )
+    .await;
+
+    let cut = match cut_result {
+        Ok(cut) => cut,
+        Err(e) => {
+            emit_failure(task);
+            return ProcessingResult::failure("Failed to cut segment", e, None);
         }
-
-        // Step 3: Compress the Final Segment
-        let compressed_filename = format!("{}_final.mp4", segment.output_name);
-        let compressed_path = Path::new(&output_dir).join(&compressed_filename);
-
-        let compression_result =
-            compress_video(final_path.clone(), compressed_path.to_str().unwrap().to_string(), compression_settings.clone()).await;
-
-        match compression_result {
-            Ok(_) => {
-                // Remove previous intermediate file
+    };
+    let mut final_path = cut.path.clone();
+
+    // Step 2: Add Bumpers (if available)
+    let playlist = segment.playlist(&final_path);
+    if playlist.len() > 1 {
+        let merge_result = merger::merge_clips(
+            &playlist,
+            output_dir,
+            None,
+            segment.transition(),
+            &task.cancel,
+            |progress| emit_segment_progress(task, "adding bumpers", progress),
+        )
+        .await;
+
+        match merge_result {
+            Ok(merged_path) => {
+                // Remove the unmerged cut segment after merging
                 let _ = std::fs::remove_file(&final_path);
-                final_path = compressed_path.to_str().unwrap().to_string();
+                final_path = merged_path;
             }
             Err(e) => {
-                results.push(ProcessingResult {
-                    success: false,
-                    output_path: Some(final_path.clone()),
-                    error_message: Some(format!("Failed to compress: {}", e)),
-                });
-                continue;
+                emit_failure(task);
+                return ProcessingResult::failure("Failed to add bumpers", e, Some(final_path));
             }
         }
 
-        // Emit final success progress
-        let elapsed_time = segment_start_time.elapsed();
-        let _ = window.emit("segment_progress", json!({
-            "index": index,
-            "total": segments.len(),
-            "status": "completed",
-            "progress": 100,
-            "estimated_time": elapsed_time.as_secs_f64() * ((segments.len() - (index + 1)) as f64)
-        }));
-
-        results.push(ProcessingResult {
-            success: true,
-            output_path: Some(final_path),
-            error_message: None,
-        });
     }
 
-    results
+    // Step 3: Compress the Final Segment
+    emit_progress(task, "compressing", 0, None);
+
+    let compression_result = encoder::compress_video(
+        &final_path,
+        output_dir,
+        compression_settings,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "compressing", progress),
+    )
+    .await;
+
+    match compression_result {
+        Ok(compressed_path) => {
+            // Remove previous intermediate file
+            let _ = std::fs::remove_file(&final_path);
+            final_path = compressed_path;
+        }
+        Err(e) => {
+            emit_failure(task);
+            return ProcessingResult::failure("Failed to compress", e, Some(final_path));
+        }
+    }
+
+    // Emit final success progress
+    let elapsed_time = segment_start_time.elapsed();
+    let estimated_time = elapsed_time.as_secs_f64() * ((task.total - (task.index + 1)) as f64);
+    emit_progress(task, "completed", 100, Some(estimated_time));
+
+    ProcessingResult::success(final_path).with_cut_range(&cut)
 }
 
 
@@ -430,14 +727,17 @@ pub async fn add_intro_with_progress(
     video_path: String,
     output_dir: String,
     settings: Option<CompressionSettings>,
-    window: tauri::Window,
-) -> Result<String, String> {
-    match merger::add_intro_with_progress(intro_path, video_path, output_dir, settings, window)
-        .await
-    {
-        Ok(output_path) => Ok(output_path),
-        Err(e) => Err(format!("Failed to add intro: {}", e)),
-    }
+    transition: Option<Transition>,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    Ok(jobs.enqueue(JobKind::AddIntro {
+        intro_path,
+        video_path,
+        output_dir,
+        settings,
+        transition: transition.unwrap_or_default(),
+        with_progress: true,
+    }))
 }
 
 #[command]
@@ -445,10 +745,49 @@ pub async fn add_intro(
     intro_path: String,
     video_path: String,
     output_dir: String,
-) -> Result<String, String> {
-    match merger::add_intro(&intro_path, &video_path, &output_dir) {
-        Ok(output_path) => Ok(output_path),
-        Err(e) => Err(format!("Failed to add intro: {}", e)),
+    transition: Option<Transition>,
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    Ok(jobs.enqueue(JobKind::AddIntro {
+        intro_path,
+        video_path,
+        output_dir,
+        settings: None,
+        transition: transition.unwrap_or_default(),
+        with_progress: false,
+    }))
+}
+
+pub async fn add_intro_task(
+    task: &Task,
+    intro_path: &str,
+    video_path: &str,
+    output_dir: &str,
+    settings: Option<CompressionSettings>,
+    transition: &Transition,
+    with_progress: bool,
+) -> ProcessingResult {
+    let result = if with_progress {
+        merger::add_intro_with_progress(
+            intro_path.to_string(),
+            video_path.to_string(),
+            output_dir.to_string(),
+            settings,
+            transition,
+            &task.cancel,
+            |progress| emit_segment_progress(task, "adding intro", progress),
+        )
+        .await
+    } else {
+        merger::add_intro(intro_path, video_path, output_dir, transition, &task.cancel).await
+    };
+
+    match result {
+        Ok(output_path) => ProcessingResult::success(output_path),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to add intro", e, None)
+        }
     }
 }
 
@@ -458,101 +797,47 @@ pub async fn compress_video(
     input_path: String,
     output_dir: String,
     settings: CompressionSettings,
-) -> Result<String, String> {
-    let ffmpeg_path = get_ffmpeg_path();
-
-    if !ffmpeg_path.exists() {
-        return Err(format!("FFmpeg not found at {:?}", ffmpeg_path));
-    }
-
-    // Extract segment name from input_path
-    let input_filename = Path::new(&input_path)
-        .file_stem()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
-    
-    let mut base_output_name = format!("{}_compressed", input_filename);
-    let mut output_dir_path = Path::new(&output_dir);
-
-    // Ensure output_dir is actually a folder, not a file
-    if output_dir_path.extension().is_some() {
-        output_dir_path = output_dir_path.parent().unwrap_or(Path::new("."));
-    }
-
-    // Ensure unique filename by appending a number if needed
-    let mut final_output_path = output_dir_path.join(format!("{}.mp4", base_output_name));
-    let mut count = 1;
-    
-    while final_output_path.exists() {
-        final_output_path = output_dir_path.join(format!("{}_{}.mp4", base_output_name, count));
-        count += 1;
-    }
-
-    #[cfg(target_os = "windows")]
-    let mut command = {
-        let mut cmd = Command::new(&ffmpeg_path);
-        cmd.creation_flags(0x08000000); // Prevents command window from popping up
-        cmd
-    };
-
-    #[cfg(not(target_os = "windows"))]
-    let mut command = Command::new(&ffmpeg_path);
-
-    command.args(&[
-        "-i",
-        &input_path,
-        "-c:v",
-        &settings.codec(),
-        "-preset",
-        &settings.preset(),
-    ]);
-
-    // Set CRF or Bitrate based on codec
-    if settings.codec() == "libx264" || settings.codec() == "libx265" {
-        command.args(&["-crf", &settings.quality().to_string()]);
-    } else {
-        let bitrate = match settings.quality() {
-            0..=10 => "8M",
-            11..=20 => "5M",
-            21..=30 => "2M",
-            _ => "1M",
-        };
-        command.args(&["-b:v", bitrate]);
-    }
-
-    command
-        .args(&[
-            "-c:a",
-            "aac",
-            "-b:a",
-            "128k",
-            "-y",
-            final_output_path.to_str().unwrap(),
-        ])
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped()) // Capture FFmpeg output
-        .stderr(Stdio::piped());
-
-    let output = command
-        .output()
-        .await
-        .map_err(|e| format!("FFmpeg execution failed: {}", e))?;
+    jobs: State<'_, JobManager>,
+) -> Result<String, Error> {
+    settings.check_overlay()?;
+
+    Ok(jobs.enqueue(JobKind::Compress {
+        input_path,
+        output_dir,
+        settings,
+    }))
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("FFmpeg compression failed: {}", stderr));
+pub async fn compress_task(
+    task: &Task,
+    input_path: &str,
+    output_dir: &str,
+    settings: &CompressionSettings,
+) -> ProcessingResult {
+    let result = encoder::compress_video(
+        input_path,
+        output_dir,
+        settings,
+        &task.cancel,
+        |progress| emit_segment_progress(task, "compressing", progress),
+    )
+    .await;
+
+    match result {
+        Ok(output_path) => ProcessingResult::success(output_path),
+        Err(e) => {
+            emit_failure(task);
+            ProcessingResult::failure("Failed to compress video", e, None)
+        }
     }
-
-    Ok(final_output_path.to_str().unwrap().to_string())
 }
 
 
 
 #[command]
-pub async fn save_video(input_path: String, output_path: String) -> Result<(), String> {
+pub async fn save_video(input_path: String, output_path: String) -> Result<(), Error> {
     match std::fs::copy(input_path, output_path) {
         Ok(_) => Ok(()),
-        Err(e) => Err(format!("Failed to save video: {}", e)),
+        Err(e) => Err(Error::new("Failed to save video", e.into())),
     }
 }
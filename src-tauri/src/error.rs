@@ -0,0 +1,184 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::Output;
+use serde::{Deserialize, Serialize};
+use crate::video::{process, validator::SegmentDiagnostic};
+
+/// Number of stderr lines kept from a failed FFmpeg run
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Error returned by commands, serialized as `{ "kind": "...", ... }` so the
+/// frontend can branch on `kind`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Error {
+    /// FFmpeg or FFprobe isn't where we looked for it
+    FfmpegNotFound { path: String },
+    /// FFprobe couldn't read the file
+    ProbeFailed {
+        path: String,
+        exit_code: Option<i32>,
+        stderr: String,
+    },
+    /// FFmpeg exited with an error we don't recognise
+    FfmpegFailed {
+        context: String,
+        exit_code: Option<i32>,
+        stderr: String,
+    },
+    /// The segment's times don't describe a range of the video
+    InvalidSegment {
+        message: String,
+        #[serde(default)]
+        diagnostics: Vec<SegmentDiagnostic>, // one per segment, when validated as a batch
+    },
+    /// The requested encoder isn't built into this FFmpeg
+    EncoderUnavailable {
+        encoder: String,
+        exit_code: Option<i32>,
+        stderr: String,
+    },
+    /// The output drive ran out of space
+    DiskFull { exit_code: Option<i32>, stderr: String },
+    Cancelled,
+    Io { message: String },
+    JobNotFound { job_id: String },
+    /// A request that doesn't fit the current state, e.g. resuming a running job
+    InvalidRequest { message: String },
+    Other { message: String },
+}
+
+impl Error {
+    /// Classify a failed FFmpeg run from its stderr
+    pub fn ffmpeg(context: &str, output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let exit_code = output.status.code();
+
+        if stderr.contains("No space left on device") {
+            return Error::DiskFull {
+                exit_code,
+                stderr: stderr_tail(&stderr),
+            };
+        }
+
+        if let Some(encoder) = unknown_encoder(&stderr) {
+            return Error::EncoderUnavailable {
+                encoder,
+                exit_code,
+                stderr: stderr_tail(&stderr),
+            };
+        }
+
+        Error::FfmpegFailed {
+            context: context.to_string(),
+            exit_code,
+            stderr: stderr_tail(&stderr),
+        }
+    }
+
+    /// A failed FFprobe run on `path`
+    pub fn probe(path: &str, output: &Output) -> Self {
+        Error::ProbeFailed {
+            path: path.to_string(),
+            exit_code: output.status.code(),
+            stderr: stderr_tail(&String::from_utf8_lossy(&output.stderr)),
+        }
+    }
+
+    /// Error for a binary that failed to start, `NotFound` means it's missing
+    pub fn spawn(program: &Path, error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::NotFound {
+            Error::FfmpegNotFound {
+                path: program.display().to_string(),
+            }
+        } else {
+            Error::Io {
+                message: format!("Failed to start {}: {}", program.display(), error),
+            }
+        }
+    }
+
+    /// Convert an error from the video modules, prefixing `context` to
+    /// errors that don't already describe themselves
+    pub fn new(context: &str, error: anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            return error.clone();
+        }
+
+        if process::is_cancelled(&error) {
+            return Error::Cancelled;
+        }
+
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            if io_error.kind() == io::ErrorKind::StorageFull {
+                return Error::DiskFull {
+                    exit_code: None,
+                    stderr: String::new(),
+                };
+            }
+
+            return Error::Io {
+                message: format!("{}: {}", context, error),
+            };
+        }
+
+        Error::Other {
+            message: format!("{}: {:#}", context, error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::FfmpegNotFound { path } => write!(f, "FFmpeg not found at {}", path),
+            Error::ProbeFailed { path, stderr, .. } => {
+                write!(f, "Could not read {}", path)?;
+                write_last_line(f, stderr)
+            }
+            Error::FfmpegFailed { context, exit_code, stderr } => {
+                write!(f, "{}", context)?;
+                if let Some(code) = exit_code {
+                    write!(f, " (exit code {})", code)?;
+                }
+                write_last_line(f, stderr)
+            }
+            Error::InvalidSegment { message, .. } => write!(f, "Invalid segment: {}", message),
+            Error::EncoderUnavailable { encoder, .. } => {
+                write!(f, "Encoder {} is not available in this FFmpeg build", encoder)
+            }
+            Error::DiskFull { .. } => write!(f, "Not enough disk space to write the output"),
+            Error::Cancelled => write!(f, "{}", process::Cancelled),
+            Error::Io { message } => write!(f, "{}", message),
+            Error::JobNotFound { job_id } => write!(f, "No job with ID {}", job_id),
+            Error::InvalidRequest { message } => write!(f, "{}", message),
+            Error::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Append the last non-empty stderr line, which is usually FFmpeg's reason
+fn write_last_line(f: &mut fmt::Formatter<'_>, stderr: &str) -> fmt::Result {
+    match stderr.lines().rev().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) => write!(f, ": {}", line),
+        None => Ok(()),
+    }
+}
+
+fn stderr_tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+}
+
+/// Name of the encoder FFmpeg couldn't find, e.g. from "Unknown encoder 'libx265'"
+fn unknown_encoder(stderr: &str) -> Option<String> {
+    let rest = stderr.split("Unknown encoder '").nth(1)?;
+    rest.split('\'').next().map(str::to_string)
+}
@@ -0,0 +1,564 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::error::Error;
+use crate::commands::analysis::{self, SilenceSettings};
+use crate::commands::media::{self, ProxySettings, SpriteSettings};
+use crate::commands::timeline::{self, TimelineClip};
+use crate::commands::video::{self, CompressionSettings, ProcessingResult, Transition, VideoSegment};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Number of FFmpeg jobs run at once unless configured otherwise
+const DEFAULT_CONCURRENCY: usize = 2;
+
+/// Finished jobs kept in the persisted history
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// The work a job performs, stored so interrupted jobs can be resumed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobKind {
+    Cut {
+        input_path: String,
+        segments: Vec<VideoSegment>,
+        output_dir: String,
+        with_progress: bool,
+    },
+    Process {
+        input_path: String,
+        segments: Vec<VideoSegment>,
+        output_dir: String,
+        compression_settings: CompressionSettings,
+    },
+    AddIntro {
+        intro_path: String,
+        video_path: String,
+        output_dir: String,
+        settings: Option<CompressionSettings>,
+        #[serde(default)]
+        transition: Transition,
+        with_progress: bool,
+    },
+    Compress {
+        input_path: String,
+        output_dir: String,
+        settings: CompressionSettings,
+    },
+    Proxy {
+        source_path: String,
+        settings: ProxySettings,
+    },
+    Thumbnails {
+        source_path: String,
+        settings: SpriteSettings,
+    },
+    RemoveSilence {
+        input_path: String,
+        output_dir: String,
+        settings: SilenceSettings,
+        compression_settings: CompressionSettings,
+    },
+    RenderTimeline {
+        clips: Vec<TimelineClip>,
+        output_dir: String,
+        output_name: String,
+        compression_settings: CompressionSettings,
+        #[serde(default)]
+        transition: Transition,
+    },
+}
+
+impl JobKind {
+    /// Number of independent tasks, one per segment for batch jobs
+    fn task_count(&self) -> usize {
+        match self {
+            JobKind::Cut { segments, .. } | JobKind::Process { segments, .. } => segments.len(),
+            JobKind::AddIntro { .. }
+            | JobKind::Compress { .. }
+            | JobKind::Proxy { .. }
+            | JobKind::Thumbnails { .. }
+            | JobKind::RemoveSilence { .. }
+            | JobKind::RenderTimeline { .. } => 1,
+        }
+    }
+
+    async fn run_task(&self, task: &Task) -> ProcessingResult {
+        match self {
+            JobKind::Cut { input_path, segments, output_dir, with_progress } => {
+                video::cut_segment_task(task, input_path, &segments[task.index], output_dir, *with_progress).await
+            }
+            JobKind::Process { input_path, segments, output_dir, compression_settings } => {
+                video::process_segment_task(task, input_path, &segments[task.index], output_dir, compression_settings)
+                    .await
+            }
+            JobKind::AddIntro { intro_path, video_path, output_dir, settings, transition, with_progress } => {
+                video::add_intro_task(
+                    task,
+                    intro_path,
+                    video_path,
+                    output_dir,
+                    settings.clone(),
+                    transition,
+                    *with_progress,
+                )
+                .await
+            }
+            JobKind::Compress { input_path, output_dir, settings } => {
+                video::compress_task(task, input_path, output_dir, settings).await
+            }
+            JobKind::Proxy { source_path, settings } => media::proxy_task(task, source_path, settings).await,
+            JobKind::Thumbnails { source_path, settings } => {
+                media::thumbnails_task(task, source_path, settings).await
+            }
+            JobKind::RemoveSilence { input_path, output_dir, settings, compression_settings } => {
+                analysis::remove_silence_task(task, input_path, output_dir, settings, compression_settings).await
+            }
+            JobKind::RenderTimeline { clips, output_dir, output_name, compression_settings, transition } => {
+                timeline::render_timeline_task(task, clips, output_dir, output_name, compression_settings, transition)
+                    .await
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    /// Finished, but at least one task failed, see `results`
+    Failed,
+    Cancelled,
+    /// The app was closed before the job finished
+    Interrupted,
+}
+
+impl JobStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub status: JobStatus,
+    pub created_at: u64, // milliseconds since the Unix epoch
+    #[serde(flatten)]
+    pub kind: JobKind,
+    /// One slot per task, `None` until the task has finished
+    pub results: Vec<Option<ProcessingResult>>,
+}
+
+/// One unit of work within a job, e.g. a single segment
+pub struct Task {
+    pub app: AppHandle,
+    pub job_id: String,
+    pub index: usize,
+    pub total: usize,
+    pub cancel: CancellationToken,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedQueue {
+    concurrency: Option<usize>,
+    jobs: Vec<JobRecord>,
+}
+
+struct JobEntry {
+    record: JobRecord,
+    cancel: CancellationToken,
+}
+
+struct Inner {
+    app: AppHandle,
+    store_path: Option<PathBuf>,
+    jobs: Mutex<HashMap<String, JobEntry>>,
+    concurrency: Mutex<usize>,
+    workers: Arc<Semaphore>,
+    /// Permits still to be taken out of `workers` after the limit was lowered
+    retiring: Mutex<usize>,
+}
+
+/// Background job queue running FFmpeg work on a bounded worker pool
+pub struct JobManager {
+    inner: Arc<Inner>,
+}
+
+impl JobManager {
+    /// Load the queue persisted by a previous session.
+    ///
+    /// Jobs that were still queued or running are marked as interrupted and
+    /// wait for `resume` instead of starting on their own.
+    pub fn load(app: AppHandle) -> Self {
+        let store_path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join("jobs.json"));
+
+        let persisted: PersistedQueue = store_path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+
+        let concurrency = persisted.concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+        let jobs = persisted
+            .jobs
+            .into_iter()
+            .map(|mut record| {
+                if !record.status.is_finished() {
+                    record.status = JobStatus::Interrupted;
+                }
+                let entry = JobEntry {
+                    record,
+                    cancel: CancellationToken::new(),
+                };
+                (entry.record.id.clone(), entry)
+            })
+            .collect();
+
+        let manager = Self {
+            inner: Arc::new(Inner {
+                app,
+                store_path,
+                jobs: Mutex::new(jobs),
+                concurrency: Mutex::new(concurrency),
+                workers: Arc::new(Semaphore::new(concurrency)),
+                retiring: Mutex::new(0),
+            }),
+        };
+        manager.inner.persist(&manager.inner.jobs.lock().unwrap());
+        manager
+    }
+
+    /// Add a job to the queue and return its ID
+    pub fn enqueue(&self, kind: JobKind) -> String {
+        let record = JobRecord {
+            id: Uuid::new_v4().to_string(),
+            status: JobStatus::Queued,
+            created_at: now_millis(),
+            results: vec![None; kind.task_count()],
+            kind,
+        };
+        let job_id = record.id.clone();
+
+        {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            jobs.insert(
+                job_id.clone(),
+                JobEntry {
+                    record,
+                    cancel: CancellationToken::new(),
+                },
+            );
+            self.inner.persist(&jobs);
+        }
+
+        Inner::start(self.inner.clone(), job_id.clone());
+        job_id
+    }
+
+    /// Requeue the unfinished tasks of an interrupted job
+    pub fn resume(&self, job_id: &str) -> Result<(), Error> {
+        {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            let entry = jobs
+                .get_mut(job_id)
+                .ok_or_else(|| Error::JobNotFound {
+                    job_id: job_id.to_string(),
+                })?;
+
+            if entry.record.status != JobStatus::Interrupted {
+                return Err(Error::InvalidRequest {
+                    message: format!("Job {} is not interrupted", job_id),
+                });
+            }
+
+            entry.record.status = JobStatus::Queued;
+            entry.cancel = CancellationToken::new();
+            self.inner.persist(&jobs);
+        }
+
+        Inner::start(self.inner.clone(), job_id.to_string());
+        Ok(())
+    }
+
+    /// Cancel a queued, running or interrupted job, returns `false` if no
+    /// such unfinished job exists
+    pub fn cancel(&self, job_id: &str) -> bool {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let Some(entry) = jobs.get_mut(job_id) else {
+            return false;
+        };
+
+        match entry.record.status {
+            JobStatus::Queued | JobStatus::Running => {
+                entry.cancel.cancel();
+                true
+            }
+            JobStatus::Interrupted => {
+                // Nothing is running, so finish the job right away
+                entry.cancel.cancel();
+                entry.record.status = JobStatus::Cancelled;
+                self.inner.fill_cancelled(&mut entry.record, &entry.cancel);
+                self.inner.emit_finished(&entry.record);
+                self.inner.persist(&jobs);
+                true
+            }
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => false,
+        }
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<JobRecord> {
+        let jobs = self.inner.jobs.lock().unwrap();
+        jobs.get(job_id).map(|entry| entry.record.clone())
+    }
+
+    /// All known jobs, oldest first
+    pub fn list(&self) -> Vec<JobRecord> {
+        let jobs = self.inner.jobs.lock().unwrap();
+        sorted_records(&jobs)
+    }
+
+    pub fn concurrency(&self) -> usize {
+        *self.inner.concurrency.lock().unwrap()
+    }
+
+    /// Change how many tasks may run at once
+    pub fn set_concurrency(&self, limit: usize) -> Result<(), Error> {
+        if limit == 0 {
+            return Err(Error::InvalidRequest {
+                message: "Concurrency must be at least 1".to_string(),
+            });
+        }
+
+        let previous = std::mem::replace(&mut *self.inner.concurrency.lock().unwrap(), limit);
+
+        if limit > previous {
+            // Cancel permits that are still waiting to be retired before adding new ones
+            let mut retiring = self.inner.retiring.lock().unwrap();
+            let cancelled = (*retiring).min(limit - previous);
+            *retiring -= cancelled;
+            self.inner.workers.add_permits(limit - previous - cancelled);
+        } else if limit < previous {
+            *self.inner.retiring.lock().unwrap() += previous - limit;
+            tauri::async_runtime::spawn(Inner::retire_permits(self.inner.clone()));
+        }
+
+        self.inner.persist(&self.inner.jobs.lock().unwrap());
+        Ok(())
+    }
+}
+
+impl Inner {
+    /// Take permits out of the pool one at a time as running tasks release
+    /// them, until `retiring` is down to zero
+    async fn retire_permits(inner: Arc<Inner>) {
+        loop {
+            let Ok(permit) = inner.workers.acquire().await else {
+                return;
+            };
+
+            let mut retiring = inner.retiring.lock().unwrap();
+            if *retiring == 0 {
+                // The limit was raised again meanwhile, give the permit back
+                return;
+            }
+            *retiring -= 1;
+            permit.forget();
+        }
+    }
+
+    /// Run the pending tasks of a job on the worker pool
+    fn start(inner: Arc<Inner>, job_id: String) {
+        let Some((kind, cancel, pending)) = ({
+            let jobs = inner.jobs.lock().unwrap();
+            jobs.get(&job_id).map(|entry| {
+                let pending: Vec<usize> = entry
+                    .record
+                    .results
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, result)| result.is_none())
+                    .map(|(index, _)| index)
+                    .collect();
+                (Arc::new(entry.record.kind.clone()), entry.cancel.clone(), pending)
+            })
+        }) else {
+            return;
+        };
+
+        tauri::async_runtime::spawn(async move {
+            let total = kind.task_count();
+            let mut handles = Vec::new();
+
+            // Take a worker for each task in order so segments start in sequence
+            for index in pending {
+                let permit = tokio::select! {
+                    permit = inner.workers.clone().acquire_owned() => permit,
+                    _ = cancel.cancelled() => break,
+                };
+                let Ok(permit) = permit else {
+                    break;
+                };
+
+                inner.update(&job_id, |record| record.status = JobStatus::Running);
+
+                let task = Task {
+                    app: inner.app.clone(),
+                    job_id: job_id.clone(),
+                    index,
+                    total,
+                    cancel: cancel.clone(),
+                };
+                let inner = inner.clone();
+                let kind = kind.clone();
+
+                handles.push(tauri::async_runtime::spawn(async move {
+                    let result = kind.run_task(&task).await;
+                    drop(permit);
+                    inner.update(&task.job_id, |record| record.results[index] = Some(result));
+                }));
+            }
+
+            for handle in handles {
+                let _ = handle.await;
+            }
+
+            inner.finish(&job_id, cancel.is_cancelled());
+        });
+    }
+
+    fn update(&self, job_id: &str, change: impl FnOnce(&mut JobRecord)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(entry) = jobs.get_mut(job_id) {
+            change(&mut entry.record);
+            self.persist(&jobs);
+        }
+    }
+
+    fn finish(&self, job_id: &str, cancelled: bool) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(entry) = jobs.get_mut(job_id) else {
+            return;
+        };
+
+        self.fill_cancelled(&mut entry.record, &entry.cancel);
+
+        let failed = entry
+            .record
+            .results
+            .iter()
+            .flatten()
+            .any(|result| !result.is_success());
+        entry.record.status = if cancelled {
+            JobStatus::Cancelled
+        } else if failed {
+            JobStatus::Failed
+        } else {
+            JobStatus::Completed
+        };
+
+        self.emit_finished(&entry.record);
+
+        prune_finished(&mut jobs);
+        self.persist(&jobs);
+    }
+
+    /// Mark tasks that never ran as cancelled, reporting each to the UI as
+    /// the tasks that did run report themselves
+    fn fill_cancelled(&self, record: &mut JobRecord, cancel: &CancellationToken) {
+        let total = record.results.len();
+
+        for (index, result) in record.results.iter_mut().enumerate() {
+            if result.is_some() {
+                continue;
+            }
+
+            *result = Some(ProcessingResult::cancelled());
+            video::emit_cancelled(&Task {
+                app: self.app.clone(),
+                job_id: record.id.clone(),
+                index,
+                total,
+                cancel: cancel.clone(),
+            });
+        }
+    }
+
+    fn emit_finished(&self, record: &JobRecord) {
+        let _ = self.app.emit(
+            "job_finished",
+            json!({
+                "job_id": record.id,
+                "status": record.status,
+                "results": record.results
+            }),
+        );
+    }
+
+    /// Write the queue to disk. Called with the jobs lock held so writes
+    /// land in the same order as the changes they record.
+    fn persist(&self, jobs: &HashMap<String, JobEntry>) {
+        let Some(path) = &self.store_path else {
+            return;
+        };
+
+        let queue = PersistedQueue {
+            concurrency: Some(*self.concurrency.lock().unwrap()),
+            jobs: sorted_records(jobs),
+        };
+        let Ok(content) = serde_json::to_vec_pretty(&queue) else {
+            return;
+        };
+
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let temp_path = path.with_extension("json.tmp");
+        if std::fs::write(&temp_path, content).is_ok() {
+            let _ = std::fs::rename(&temp_path, path);
+        }
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<String, JobEntry>) {
+    let mut finished: Vec<(u64, String)> = jobs
+        .values()
+        .filter(|entry| entry.record.status.is_finished())
+        .map(|entry| (entry.record.created_at, entry.record.id.clone()))
+        .collect();
+
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort();
+        for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+fn sorted_records(jobs: &HashMap<String, JobEntry>) -> Vec<JobRecord> {
+    let mut records: Vec<JobRecord> = jobs.values().map(|entry| entry.record.clone()).collect();
+    records.sort_by_key(|record| record.created_at);
+    records
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
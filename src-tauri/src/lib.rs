@@ -0,0 +1,129 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+mod commands;
+mod error;
+mod jobs;
+mod server;
+mod utils;
+mod video;
+use commands::analysis::{
+    detect_black_frames, detect_frozen_frames, detect_scenes, detect_silence, remove_silence,
+};
+use commands::file::{select_directory, select_file};
+use commands::timeline::render_timeline;
+use commands::jobs::{
+    cancel_job, get_job, get_job_concurrency, list_jobs, resume_job, set_job_concurrency,
+};
+use commands::media::{extract_frame, generate_proxy, generate_thumbnails, get_proxy, get_waveform};
+use commands::video::{
+    add_intro, add_intro_with_progress, compress_video, cut_video, cut_video_with_progress, get_keyframes, get_video_metadata, load_video, process_video_with_progress, save_video, validate_segments
+};
+use server::{get_frame_url, get_preview_url, get_proxy_url, get_thumbnails_url, get_video_url, start_video_server};
+use std::collections::HashMap;
+use tauri::{command, Manager};
+use std::process::Stdio;
+use utils::{get_ffmpeg_path, get_ffprobe_path, new_command};
+
+#[command]
+fn check_dependencies() -> Result<HashMap<String, String>, String> {
+    let mut dependencies = HashMap::new();
+
+    // Get FFmpeg and FFprobe paths using utility functions
+    let ffmpeg_path = get_ffmpeg_path();
+    let ffprobe_path = get_ffprobe_path();
+
+    // Function to check if a command runs successfully without showing a window
+    fn check_command_hidden(path: &std::path::Path) -> bool {
+        path.exists()
+            && new_command(path)
+                .arg("-version")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map(|mut child| child.wait().is_ok())
+                .unwrap_or(false)
+    }
+
+    // Check if ffmpeg and ffprobe exist and are executable
+    let ffmpeg_exists = check_command_hidden(&ffmpeg_path);
+    let ffprobe_exists = check_command_hidden(&ffprobe_path);
+
+    // Store results in HashMap
+    dependencies.insert(
+        "ffmpeg".to_string(),
+        format!(
+            "{} (Exists: {})",
+            ffmpeg_path.display(),
+            ffmpeg_exists
+        ),
+    );
+
+    dependencies.insert(
+        "ffprobe".to_string(),
+        format!(
+            "{} (Exists: {})",
+            ffprobe_path.display(),
+            ffprobe_exists
+        ),
+    );
+
+    Ok(dependencies)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .setup(|app| {
+            if let Ok(dir) = app.path().app_cache_dir() {
+                utils::set_cache_dir(dir);
+            }
+            app.manage(jobs::JobManager::load(app.handle().clone()));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            load_video,
+            cut_video,
+            cut_video_with_progress,
+            process_video_with_progress,
+            add_intro,
+            add_intro_with_progress,
+            compress_video,
+            save_video,
+            get_video_metadata,
+            get_keyframes,
+            validate_segments,
+            select_file,
+            select_directory,
+            check_dependencies,
+            list_jobs,
+            get_job,
+            cancel_job,
+            resume_job,
+            get_job_concurrency,
+            set_job_concurrency,
+            start_video_server,
+            get_video_url,
+            get_proxy_url,
+            get_preview_url,
+            generate_proxy,
+            get_proxy,
+            extract_frame,
+            get_frame_url,
+            generate_thumbnails,
+            get_thumbnails_url,
+            get_waveform,
+            detect_scenes,
+            detect_silence,
+            remove_silence,
+            detect_black_frames,
+            detect_frozen_frames,
+            render_timeline
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
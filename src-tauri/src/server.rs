@@ -0,0 +1,441 @@
+// Copyright (c) 2025 Abdul Khadhar. All rights reserved.
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    routing::get,
+    Router,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+use tokio::net::TcpListener;
+use tokio_util::io::ReaderStream;
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::error::Error;
+use crate::commands::media::ImageFormat;
+use crate::video::{
+    frames,
+    preview::{self, DEFAULT_PREVIEW_HEIGHT},
+    proxy, sprites,
+};
+
+// Base URL of the running server, `None` until it is started
+static SERVER_URL: Mutex<Option<String>> = Mutex::new(None);
+
+/// Files the user opened during this launch. Only these are served, by an
+/// opaque ID and behind a token that changes every launch, so other local
+/// processes and browser tabs can't read arbitrary files through the server.
+struct Session {
+    token: String,
+    files: Mutex<HashMap<String, PathBuf>>,
+}
+
+fn session() -> &'static Session {
+    static SESSION: OnceLock<Session> = OnceLock::new();
+    SESSION.get_or_init(|| Session {
+        token: Uuid::new_v4().simple().to_string(),
+        files: Mutex::new(HashMap::new()),
+    })
+}
+
+/// Allow the server to serve `path`, returns its ID
+pub fn allow_file(path: &str) -> String {
+    let path = canonical(path);
+    let mut files = session().files.lock().unwrap();
+
+    if let Some(id) = file_id(&files, &path) {
+        return id;
+    }
+
+    let id = Uuid::new_v4().simple().to_string();
+    files.insert(id.clone(), path);
+    id
+}
+
+fn canonical(path: &str) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+fn file_id(files: &HashMap<String, PathBuf>, path: &std::path::Path) -> Option<String> {
+    files
+        .iter()
+        .find(|(_, allowed)| allowed.as_path() == path)
+        .map(|(id, _)| id.clone())
+}
+
+fn allowed_file(token: &str, id: &str) -> Option<PathBuf> {
+    let session = session();
+    if token != session.token {
+        return None;
+    }
+    session.files.lock().unwrap().get(id).cloned()
+}
+
+#[tauri::command]
+pub async fn start_video_server(port: u16) -> Result<String, String> {
+    // Check if server is already running
+    if let Some(url) = SERVER_URL.lock().unwrap().clone() {
+        return Ok(url);
+    }
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+    // Try to bind to the port, or any free one if something else holds it
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .map_err(|e| format!("Failed to bind a local port: {}", e))?
+        }
+        Err(e) => return Err(format!("Failed to bind to port {}: {}", port, e)),
+    };
+
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read server address: {}", e))?
+        .port();
+
+    // Create a new Axum router
+    let app = Router::new()
+        .route("/video/{token}/{id}", get(serve_video))
+        .route("/proxy/{token}/{id}", get(serve_proxy))
+        .route("/preview/{token}/{id}", get(serve_preview))
+        .route("/frame/{token}/{id}", get(serve_frame))
+        .route("/sprites/{token}/{id}/{file}", get(serve_sprite));
+
+    let mut server_url = SERVER_URL.lock().unwrap();
+    if let Some(url) = server_url.clone() {
+        // Another call started the server while we were binding
+        return Ok(url);
+    }
+
+    // Spawn the server on a background task
+    tauri::async_runtime::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let url = format!("http://127.0.0.1:{}", port);
+    *server_url = Some(url.clone());
+    Ok(url)
+}
+
+/// URL the player can load `path` from, for files opened with `load_video`
+/// or `select_file`
+#[tauri::command]
+pub fn get_video_url(path: String) -> Result<String, Error> {
+    media_url("video", &path)
+}
+
+/// URL of the proxy of `path`, or `None` until one has been generated. The
+/// player switches to it explicitly, `get_video_url` always serves the original.
+#[tauri::command]
+pub fn get_proxy_url(path: String) -> Result<Option<String>, Error> {
+    if proxy::existing_proxy(&path).is_none() {
+        return Ok(None);
+    }
+    media_url("proxy", &path).map(Some)
+}
+
+/// URL of still frames of `path`. Append `?t=<seconds>`, and optionally
+/// `&width=<px>&format=jpeg|png|webp`.
+#[tauri::command]
+pub fn get_frame_url(path: String) -> Result<String, Error> {
+    media_url("frame", &path)
+}
+
+/// URL of the WebVTT thumbnails index of `path`. Sprite sheets are resolved
+/// relative to it. Returns 404 until the thumbnails job has finished.
+#[tauri::command]
+pub fn get_thumbnails_url(path: String) -> Result<String, Error> {
+    Ok(format!("{}/{}", media_url("sprites", &path)?, sprites::THUMBNAILS_VTT))
+}
+
+/// URL of a transcoded preview of `path`, for codecs the webview can't play.
+/// Append `?t=<seconds>` to start at a given time.
+#[tauri::command]
+pub fn get_preview_url(path: String) -> Result<String, Error> {
+    media_url("preview", &path)
+}
+
+fn media_url(route: &str, path: &str) -> Result<String, Error> {
+    let base = SERVER_URL.lock().unwrap().clone().ok_or_else(|| Error::InvalidRequest {
+        message: "The video server is not running".to_string(),
+    })?;
+
+    let id = file_id(&session().files.lock().unwrap(), &canonical(path))
+        .ok_or_else(|| Error::InvalidRequest {
+            message: format!("{} has not been opened in this session", path),
+        })?;
+
+    Ok(format!("{}/{}/{}/{}", base, route, session().token, id))
+}
+
+async fn serve_video(Path((token, id)): Path<(String, String)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    // Anything not explicitly opened by the user doesn't exist as far as the server is concerned
+    let path = allowed_file(&token, &id).ok_or(StatusCode::NOT_FOUND)?;
+
+    serve_file(&path, &headers).await
+}
+
+async fn serve_proxy(Path((token, id)): Path<(String, String)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let path = allowed_file(&token, &id).ok_or(StatusCode::NOT_FOUND)?;
+    let proxy = path.to_str().and_then(proxy::existing_proxy).ok_or(StatusCode::NOT_FOUND)?;
+
+    serve_file(&proxy, &headers).await
+}
+
+#[derive(Deserialize)]
+struct PreviewQuery {
+    t: Option<f64>,      // start time in seconds
+    height: Option<u32>, // maximum height in pixels
+}
+
+async fn serve_preview(
+    Path((token, id)): Path<(String, String)>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Response<Body>, StatusCode> {
+    let path = allowed_file(&token, &id).ok_or(StatusCode::NOT_FOUND)?;
+
+    let height = query.height.unwrap_or(DEFAULT_PREVIEW_HEIGHT).clamp(144, 2160);
+    let stream = preview::start_preview(&path, query.t.unwrap_or_default(), height)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // The length isn't known up front and seeking is done by requesting a new `t`
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "video/mp4")
+        .header(header::ACCEPT_RANGES, "none")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(ReaderStream::new(stream)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+struct FrameQuery {
+    t: Option<f64>,     // time in seconds
+    width: Option<u32>, // in pixels, keeps the aspect ratio
+    format: Option<ImageFormat>,
+}
+
+async fn serve_frame(
+    Path((token, id)): Path<(String, String)>,
+    Query(query): Query<FrameQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    let path = allowed_file(&token, &id).ok_or(StatusCode::NOT_FOUND)?;
+    let path = path.to_str().ok_or(StatusCode::NOT_FOUND)?;
+
+    let width = query.width.map(|width| width.clamp(16, 3840));
+    let frame = frames::extract_frame(path, query.t.unwrap_or_default(), width, query.format.unwrap_or_default())
+        .await
+        .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+    serve_file(&frame, &headers).await
+}
+
+async fn serve_sprite(
+    Path((token, id, file)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    let path = allowed_file(&token, &id).ok_or(StatusCode::NOT_FOUND)?;
+    let sprite = sprites::sprite_file(&path, &file).ok_or(StatusCode::NOT_FOUND)?;
+
+    serve_file(&sprite, &headers).await
+}
+
+/// Stream a file from disk, honouring `Range` and conditional requests so the
+/// player can seek without downloading the whole file
+async fn serve_file(path: &std::path::Path, headers: &HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let mut file = File::open(path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let metadata = file.metadata().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !metadata.is_file() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(size, modified);
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    let response = || {
+        let mut builder = Response::builder()
+            .header(header::CONTENT_TYPE, mime_type(path))
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::ETAG, &etag);
+        if let Some(last_modified) = &last_modified {
+            builder = builder.header(header::LAST_MODIFIED, last_modified);
+        }
+        builder
+    };
+
+    // The player already has this version of the file
+    if header_value(headers, header::IF_NONE_MATCH).is_some_and(|tags| tags.split(',').any(|t| t.trim() == etag || t.trim() == "*")) {
+        return response()
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // A range for a different version of the file means "send it all"
+    let range_applies = header_value(headers, header::IF_RANGE).is_none_or(|tag| tag == etag);
+    let range = header_value(headers, header::RANGE)
+        .filter(|_| range_applies)
+        .and_then(|value| parse_range(value, size));
+
+    let (status, start, length) = match range {
+        None => (StatusCode::OK, 0, size),
+        Some(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        Some(None) => {
+            return response()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut builder = response()
+        .status(status)
+        .header(header::CONTENT_LENGTH, length);
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, start + length - 1, size),
+        );
+    }
+
+    // Read from disk as the player consumes the body instead of buffering it
+    builder
+        .body(Body::from_stream(ReaderStream::new(file.take(length))))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Parse a single `bytes=` range into inclusive offsets.
+///
+/// Returns `None` for headers we don't handle (so the whole file is sent) and
+/// `Some(None)` for ranges outside the file.
+fn parse_range(value: &str, size: u64) -> Option<Option<(u64, u64)>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+
+    // Browsers only ask for one range at a time for media
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        let suffix = end.parse::<u64>().ok()?;
+        if suffix == 0 {
+            return Some(None);
+        }
+        (size.saturating_sub(suffix), size.checked_sub(1)?)
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            size.saturating_sub(1)
+        } else {
+            end.parse::<u64>().ok()?.min(size.saturating_sub(1))
+        };
+        (start, end)
+    };
+
+    if range.0 >= size || range.0 > range.1 {
+        return Some(None);
+    }
+
+    Some(Some(range))
+}
+
+/// Validator built from the file size and modification time
+fn entity_tag(size: u64, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", size, modified)
+}
+
+fn mime_type(path: &std::path::Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("mp4") | Some("m4v") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("mkv") => "video/x-matroska",
+        Some("webm") => "video/webm",
+        Some("avi") => "video/x-msvideo",
+        Some("ts") => "video/mp2t",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("vtt") => "text/vtt",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Some((0, 99))));
+        // The end is clamped to the file
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some(Some((900, 999))));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=500-", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range(" bytes=0- ", 1000), Some(Some((0, 999))));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Some((900, 999))));
+        // A suffix longer than the file means the whole file
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Some((0, 999))));
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(parse_range("bytes=500-100", 1000), Some(None));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(None));
+        assert_eq!(parse_range("bytes=0-", 0), Some(None));
+    }
+
+    #[test]
+    fn ignores_multiple_and_malformed_ranges() {
+        assert_eq!(parse_range("bytes=0-99,200-299", 1000), None);
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=abc-", 1000), None);
+        assert_eq!(parse_range("bytes=100", 1000), None);
+    }
+}
@@ -1,22 +1,152 @@
-use std::env::current_exe;
-use std::path::PathBuf;
+use std::env::{self, current_exe};
+use std::ffi::OsStr;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use crate::error::Error;
 
-/// Get the correct installed FFmpeg path
+/// Windows `CREATE_NO_WINDOW` flag, keeps FFmpeg from flashing a console window
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Root directory for generated media such as proxies, set once at startup
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the correct FFmpeg path
 pub fn get_ffmpeg_path() -> PathBuf {
-    let app_dir = current_exe()
+    locate_binary("ffmpeg", "EDDIT_FFMPEG")
+}
+
+/// Get the correct FFprobe path
+pub fn get_ffprobe_path() -> PathBuf {
+    locate_binary("ffprobe", "EDDIT_FFPROBE")
+}
+
+/// Resolve a binary from the env override, the bundled `bin` folder or `PATH`.
+///
+/// When nothing is found the bundled location is returned so callers can
+/// report where the binary was expected.
+fn locate_binary(name: &str, env_var: &str) -> PathBuf {
+    if let Some(path) = env::var_os(env_var).filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    let file_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    let bundled = bundled_bin_dir().join(&file_name);
+    if bundled.is_file() {
+        return bundled;
+    }
+
+    find_in_path(OsStr::new(&file_name)).unwrap_or(bundled)
+}
+
+/// Directory holding the binaries shipped with the installer
+fn bundled_bin_dir() -> PathBuf {
+    current_exe()
         .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_else(|| PathBuf::from("C:\\Program Files\\eddit")); // Ensure fallback
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+        .join("bin")
+}
 
-    app_dir.join("bin").join("ffmpeg.exe") 
+fn find_in_path(file_name: &OsStr) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| candidate.is_file())
 }
 
-/// Get the correct installed FFprobe path
-pub fn get_ffprobe_path() -> PathBuf {
-    let app_dir = current_exe()
+/// Create a command that won't open a console window on Windows
+pub fn new_command(program: &Path) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(program);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+}
+
+/// Async FFmpeg command, or `FfmpegNotFound` if the binary is missing
+pub fn ffmpeg_command() -> Result<tokio::process::Command, Error> {
+    let ffmpeg_path = get_ffmpeg_path();
+
+    if !ffmpeg_path.exists() {
+        return Err(Error::FfmpegNotFound {
+            path: ffmpeg_path.display().to_string(),
+        });
+    }
+
+    Ok(tokio::process::Command::from(new_command(&ffmpeg_path)))
+}
+
+/// Use `dir` as the root for generated media. Only the first call has an effect.
+pub fn set_cache_dir(dir: PathBuf) {
+    let _ = CACHE_DIR.set(dir);
+}
+
+/// Cache subdirectory `name`, created if needed
+pub fn cache_dir(name: &str) -> std::io::Result<PathBuf> {
+    let dir = CACHE_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| env::temp_dir().join("eddit"))
+        .join(name);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Reserve `<dir>/<name>.mp4`, numbered `<name>_1.mp4` and so on if it
+/// already exists.
+///
+/// The file is created empty so concurrent jobs never pick the same name,
+/// FFmpeg then overwrites it.
+pub fn unique_output_path(dir: impl AsRef<Path>, name: &str) -> std::io::Result<PathBuf> {
+    let dir = dir.as_ref();
+    let mut path = dir.join(format!("{}.mp4", name));
+    let mut count = 1;
+
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                path = dir.join(format!("{}_{}.mp4", name, count));
+                count += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Await `work`, which writes the output reserved at `path` with
+/// `unique_output_path`. If it fails or is cancelled the reservation is
+/// removed, so no empty or partial file is left in the output folder.
+pub async fn fill_reserved_output<T>(path: &Path, work: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
+    let result = work.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    result
+}
+
+/// Stable key for a file's current contents, built from its canonical path,
+/// size and modification time. Changes whenever the file is rewritten.
+pub fn file_fingerprint(path: &str) -> std::io::Result<String> {
+    let canonical = std::fs::canonicalize(path)?;
+    let metadata = std::fs::metadata(&canonical)?;
+    let modified = metadata
+        .modified()
         .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_else(|| PathBuf::from("C:\\Program Files\\eddit")); // Ensure fallback
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
 
-    app_dir.join("bin").join("ffprobe.exe") 
+    let key = format!("{}|{}|{}", canonical.display(), metadata.len(), modified);
+    Ok(uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, key.as_bytes()).simple().to_string())
 }
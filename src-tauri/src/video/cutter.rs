@@ -1,41 +1,48 @@
-use std::{os::windows::process::CommandExt, path::Path};
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
 use anyhow::{Result, anyhow};
 use serde_json::Value;
-use super::super::commands::video::VideoMetadata;
-use crate::utils::{get_ffmpeg_path, get_ffprobe_path}; 
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use super::super::commands::video::{
+    AudioStream, Chapter, CompressionSettings, CutMode, Fade, SubtitleStream, VideoMetadata, VideoSegment,
+};
+use super::{detector, encoder, merger};
+use super::process::{self, Progress, PROGRESS_ARGS};
+use crate::error::Error;
+use crate::utils::{ffmpeg_command, fill_reserved_output, get_ffprobe_path, new_command, unique_output_path};
 
-pub fn get_metadata(video_path: &str) -> Result<VideoMetadata> {
+pub async fn get_metadata(video_path: &str) -> Result<VideoMetadata> {
     let ffprobe_path = get_ffprobe_path();
 
-    #[cfg(target_os = "windows")]
-    let mut cmd = {
-        let mut command = Command::new(ffprobe_path);
-        command.creation_flags(0x08000000); // CREATE_NO_WINDOW flag
-        command
-    };
-
-    #[cfg(not(target_os = "windows"))]
-    let mut cmd = Command::new(ffprobe_path);
+    let mut cmd = Command::from(new_command(&ffprobe_path));
 
     let output = cmd
-        .args(&[
-            "-v", "quiet",
+        .args([
+            "-v", "error",
             "-print_format", "json",
             "-show_format",
             "-show_streams",
+            "-show_chapters",
             video_path
         ])
         .stdin(Stdio::null())
         .stdout(Stdio::piped())  // Capture output for parsing
-        .stderr(Stdio::null())   // Hide errors from appearing in the console
-        .output()?;
+        .stderr(Stdio::piped())  // Keep the reason if ffprobe fails
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::spawn(&ffprobe_path, e))?;
 
     if !output.status.success() {
-        return Err(anyhow!("ffprobe command failed"));
+        return Err(Error::probe(video_path, &output).into());
     }
 
     let json_output: Value = serde_json::from_slice(&output.stdout)?;
+    let format = &json_output["format"];
     let streams = json_output["streams"]
         .as_array()
         .ok_or_else(|| anyhow!("No streams found"))?;
@@ -45,7 +52,7 @@ pub fn get_metadata(video_path: &str) -> Result<VideoMetadata> {
         .find(|s| s["codec_type"].as_str().unwrap_or("") == "video")
         .ok_or_else(|| anyhow!("No video stream found"))?;
 
-    let duration_str = json_output["format"]["duration"]
+    let duration_str = format["duration"]
         .as_str()
         .ok_or_else(|| anyhow!("No duration found"))?;
     let duration = duration_str.parse::<f64>()?;
@@ -60,84 +67,653 @@ pub fn get_metadata(video_path: &str) -> Result<VideoMetadata> {
     let frame_rate_str = video_stream["r_frame_rate"]
         .as_str()
         .ok_or_else(|| anyhow!("No frame rate found"))?;
-    let parts: Vec<&str> = frame_rate_str.split('/').collect();
-    if parts.len() != 2 {
-        return Err(anyhow!("Invalid frame rate format"));
-    }
-
-    let numerator = parts[0].parse::<f64>()?;
-    let denominator = parts[1].parse::<f64>()?;
-    let framerate = numerator / denominator;
+    let framerate = parse_rate(frame_rate_str).ok_or_else(|| anyhow!("Invalid frame rate format"))?;
 
     let codec = video_stream["codec_name"]
         .as_str()
         .unwrap_or("unknown")
         .to_string();
 
+    // r_frame_rate is the lowest rate all timestamps fit, avg_frame_rate the
+    // real average. They only drift apart when frame durations vary.
+    let variable_frame_rate = video_stream["avg_frame_rate"]
+        .as_str()
+        .and_then(parse_rate)
+        .is_some_and(|avg| (avg - framerate).abs() > 0.01);
+
+    let audio_streams = streams_of_type(streams, "audio")
+        .map(|stream| AudioStream {
+            index: stream["index"].as_u64().unwrap_or_default() as u32,
+            codec: stream["codec_name"].as_str().unwrap_or("unknown").to_string(),
+            channels: stream["channels"].as_u64().unwrap_or_default() as u32,
+            channel_layout: string_field(&stream["channel_layout"]),
+            sample_rate: number_field(&stream["sample_rate"]),
+            bit_rate: number_field(&stream["bit_rate"]),
+            language: string_field(&stream["tags"]["language"]),
+        })
+        .collect();
+
+    let subtitle_streams = streams_of_type(streams, "subtitle")
+        .map(|stream| SubtitleStream {
+            index: stream["index"].as_u64().unwrap_or_default() as u32,
+            codec: stream["codec_name"].as_str().unwrap_or("unknown").to_string(),
+            language: string_field(&stream["tags"]["language"]),
+            title: string_field(&stream["tags"]["title"]),
+        })
+        .collect();
+
+    let chapters = json_output["chapters"]
+        .as_array()
+        .map(|chapters| {
+            chapters
+                .iter()
+                .map(|chapter| Chapter {
+                    start: number_field(&chapter["start_time"]).unwrap_or_default(),
+                    end: number_field(&chapter["end_time"]).unwrap_or_default(),
+                    title: string_field(&chapter["tags"]["title"]),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let tags = format["tags"]
+        .as_object()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+
     Ok(VideoMetadata {
         duration,
         width,
         height,
         framerate,
         codec,
+        format: format["format_name"].as_str().unwrap_or("unknown").to_string(),
+        bit_rate: number_field(&format["bit_rate"]),
+        video_bit_rate: number_field(&video_stream["bit_rate"]),
+        pixel_format: string_field(&video_stream["pix_fmt"]),
+        color_space: string_field(&video_stream["color_space"]),
+        color_transfer: string_field(&video_stream["color_transfer"]),
+        color_primaries: string_field(&video_stream["color_primaries"]),
+        rotation: rotation(video_stream),
+        sample_aspect_ratio: string_field(&video_stream["sample_aspect_ratio"]),
+        variable_frame_rate,
+        audio_streams,
+        subtitle_streams,
+        chapters,
+        tags,
     })
 }
 
+fn streams_of_type<'a>(streams: &'a [Value], codec_type: &'a str) -> impl Iterator<Item = &'a Value> {
+    streams
+        .iter()
+        .filter(move |s| s["codec_type"].as_str() == Some(codec_type))
+}
+
+/// Parse a rational like "30000/1001"
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (numerator, denominator) = rate.split_once('/')?;
+    let numerator = numerator.parse::<f64>().ok()?;
+    let denominator = denominator.parse::<f64>().ok()?;
+    (denominator != 0.0).then(|| numerator / denominator)
+}
+
+/// ffprobe reports most numbers as JSON strings
+fn number_field<T: std::str::FromStr>(value: &Value) -> Option<T> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn string_field(value: &Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty() && *s != "unknown").map(str::to_string)
+}
+
+/// Display rotation from the display matrix side data, or the legacy `rotate` tag
+fn rotation(video_stream: &Value) -> i32 {
+    let from_side_data = video_stream["side_data_list"]
+        .as_array()
+        .and_then(|list| list.iter().find_map(|data| data["rotation"].as_f64()));
 
-pub fn cut_segment(
+    let degrees = from_side_data
+        .or_else(|| number_field::<f64>(&video_stream["tags"]["rotate"]).map(|r| -r))
+        .unwrap_or_default();
+
+    // The display matrix is counter-clockwise, normalise to 0..360 clockwise
+    (-degrees.round() as i32).rem_euclid(360)
+}
+
+/// A cut segment and the range of the source it actually covers
+pub struct CutOutput {
+    pub path: String,
+    pub actual_start: f64,
+    pub actual_end: f64,
+}
+
+pub async fn cut_segment(
     input_path: &str,
-    start_time: f64,
-    end_time: f64,
+    segment: &VideoSegment,
     output_dir: &str,
-    output_name: &str,
-) -> Result<String> {
-    let ffmpeg_path = get_ffmpeg_path();
-
-    if !ffmpeg_path.exists() {
-        return Err(anyhow!("FFmpeg not found at {:?}", ffmpeg_path));
+    settings: &CompressionSettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<CutOutput> {
+    if !(segment.end_time() > segment.start_time() && segment.start_time() >= 0.0) {
+        return Err(Error::InvalidSegment {
+            message: format!("{} to {} is not a valid range", segment.start_time(), segment.end_time()),
+            diagnostics: Vec::new(),
+        }
+        .into());
     }
 
-    let duration = end_time - start_time;
-    
+    let (start_time, end_time) = if segment.trim_black_edges() {
+        detector::trim_dead_edges(input_path, segment.start_time(), segment.end_time(), cancel).await?
+    } else {
+        (segment.start_time(), segment.end_time())
+    };
+
     // Ensure unique filename
-    let mut output_path = Path::new(output_dir).join(format!("{}_segment.mp4", output_name));
-    let mut counter = 1;
-    while output_path.exists() {
-        output_path = Path::new(output_dir).join(format!("{}_segment_{}.mp4", output_name, counter));
-        counter += 1;
+    let output_path = unique_output_path(output_dir, &format!("{}_segment", segment.output_name()))?;
+
+    let actual_start = fill_reserved_output(&output_path, async {
+        let cut = Cut {
+            input_path,
+            start_time,
+            duration: end_time - start_time,
+            output_path: &output_path,
+            fade_in: segment.fade_in(),
+            fade_out: segment.fade_out(),
+        };
+
+        // Fades are filters, which need decoded frames, so stream copy is out
+        let cut_mode = if cut.fade_in.is_none() && cut.fade_out.is_none() {
+            segment.cut_mode()
+        } else {
+            CutMode::Accurate
+        };
+
+        let actual_start = match cut_mode {
+            CutMode::Copy => {
+                cut_copy(&cut, cancel, on_progress).await?;
+
+                // Stream copy starts at the keyframe at or before the requested time
+                match keyframe_before(input_path, cut.start_time, cancel).await {
+                    Err(e) if process::is_cancelled(&e) => return Err(e),
+                    result => result.ok().flatten().unwrap_or(cut.start_time),
+                }
+            }
+            CutMode::Accurate => {
+                cut_accurate(&cut, settings, cancel, on_progress).await?;
+                cut.start_time
+            }
+            CutMode::Smart => {
+                cut_smart(&cut, settings, cancel, on_progress).await?;
+                cut.start_time
+            }
+        };
+
+        Ok(actual_start)
+    })
+    .await?;
+
+    let output_path = output_path.to_str().unwrap().to_string();
+
+    // The output's own duration tells where it really ends in the source
+    let actual_duration = get_metadata(&output_path)
+        .await
+        .map(|m| m.duration)
+        .unwrap_or(end_time - actual_start);
+
+    Ok(CutOutput {
+        path: output_path,
+        actual_start,
+        actual_end: actual_start + actual_duration,
+    })
+}
+
+/// Timestamps closer than this are treated as the same frame
+const TIME_EPSILON: f64 = 0.001;
+
+struct Cut<'a> {
+    input_path: &'a str,
+    start_time: f64,
+    duration: f64,
+    output_path: &'a Path,
+    fade_in: &'a Fade,
+    fade_out: &'a Fade,
+}
+
+/// Stream copy, fast but the start snaps to a keyframe
+async fn cut_copy(
+    cut: &Cut<'_>,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let mut cmd = ffmpeg_command()?;
+
+    // Input seeking jumps straight to the keyframe at or before the start
+    // instead of reading up to it and dropping packets until the next one
+    cmd.args(PROGRESS_ARGS).args([
+        "-ss", &cut.start_time.to_string(),
+        "-i", cut.input_path,
+        "-t", &cut.duration.to_string(),
+        "-c:v", "copy",
+        "-c:a", "copy",
+        "-avoid_negative_ts", "make_zero",
+        "-y",
+        cut.output_path.to_str().unwrap(),
+    ]);
+
+    run_cut(cmd, cut.output_path, cut.duration, cancel, on_progress).await
+}
+
+/// Full re-encode with frame-exact boundaries
+async fn cut_accurate(
+    cut: &Cut<'_>,
+    settings: &CompressionSettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args(PROGRESS_ARGS)
+        .args([
+            "-ss", &cut.start_time.to_string(),
+            "-i", cut.input_path,
+            "-t", &cut.duration.to_string(),
+        ])
+        .args(fade_args(cut).await?)
+        .args(encoder::video_codec_args(settings))
+        .args([
+            "-c:a", "aac",
+            "-b:a", "192k",
+            "-y",
+            cut.output_path.to_str().unwrap(),
+        ]);
+
+    run_cut(cmd, cut.output_path, cut.duration, cancel, on_progress).await
+}
+
+/// `-vf`/`-af` arguments applying the cut's fades, timed from the cut's start
+async fn fade_args(cut: &Cut<'_>) -> Result<Vec<String>> {
+    let fades = |filter: &str, fade_in: f64, fade_out: f64| {
+        let mut fades = Vec::new();
+        if fade_in > 0.0 {
+            fades.push(format!("{}=t=in:st=0:d={}", filter, fade_in.min(cut.duration)));
+        }
+        if fade_out > 0.0 {
+            let fade_out = fade_out.min(cut.duration);
+            fades.push(format!("{}=t=out:st={}:d={}", filter, cut.duration - fade_out, fade_out));
+        }
+        fades.join(",")
+    };
+
+    let mut args = Vec::new();
+
+    let video = fades("fade", cut.fade_in.video, cut.fade_out.video);
+    if !video.is_empty() {
+        args.extend(["-vf".to_string(), video]);
     }
 
-    #[cfg(target_os = "windows")]
-    let mut cmd = {
-        let mut command = Command::new(&ffmpeg_path);
-        command.creation_flags(0x08000000); // CREATE_NO_WINDOW flag
-        command
+    let audio = fades("afade", cut.fade_in.audio, cut.fade_out.audio);
+    // An audio filter without an audio stream is an error
+    if !audio.is_empty() && !get_metadata(cut.input_path).await?.audio_streams.is_empty() {
+        args.extend(["-af".to_string(), audio]);
+    }
+
+    Ok(args)
+}
+
+/// Re-encode only the partial GOPs at both ends and stream copy the rest.
+///
+/// Falls back to an accurate cut when the codec can't be matched or the
+/// segment doesn't span two keyframes.
+async fn cut_smart(
+    cut: &Cut<'_>,
+    settings: &CompressionSettings,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    // The re-encoded ends have to be decodable with the copied middle
+    let parameters = stream_parameters(cut.input_path).await?;
+    let Some(encoder_args) = matching_encoder_args(&parameters, settings) else {
+        return cut_accurate(cut, settings, cancel, on_progress).await;
     };
 
-    #[cfg(not(target_os = "windows"))]
-    let mut cmd = Command::new(&ffmpeg_path);
+    let end_time = cut.start_time + cut.duration;
+    let keyframes = keyframes(cut.input_path, cut.start_time, end_time, cancel).await?;
+    let first = keyframes.iter().copied().find(|&t| t >= cut.start_time - TIME_EPSILON);
+    let last = keyframes.iter().copied().rev().find(|&t| t < end_time - TIME_EPSILON);
 
-    let output = cmd
-        .args(&[
-            "-i", input_path,
-            "-ss", &start_time.to_string(),
-            "-t", &duration.to_string(),
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) if last > first => (first, last),
+        _ => return cut_accurate(cut, settings, cancel, on_progress).await,
+    };
+
+    // (start, duration, stream copy)
+    let mut pieces = Vec::new();
+    if first - cut.start_time > TIME_EPSILON {
+        pieces.push((cut.start_time, first - cut.start_time, false));
+    }
+    pieces.push((first, last - first, true));
+    pieces.push((last, end_time - last, false));
+
+    // Pieces go next to the reserved output, which makes their names unique
+    // and keeps them on the disk chosen for the output rather than the system one.
+    // MPEG-TS keeps each piece's SPS/PPS in-band, whereas MP4 would only
+    // keep the first piece's and break decoding of the others.
+    let piece_paths: Vec<PathBuf> = (0..pieces.len())
+        .map(|i| cut.output_path.with_extension(format!("smart{}.ts", i)))
+        .collect();
+    let list_path = cut.output_path.with_extension("smart.txt");
+
+    let result = async {
+        let mut offset = 0.0;
+
+        for ((start, duration, copy), piece_path) in pieces.iter().zip(&piece_paths) {
+            let mut cmd = ffmpeg_command()?;
+
+            cmd.args(PROGRESS_ARGS).args([
+                "-ss", &start.to_string(),
+                "-i", cut.input_path,
+                "-t", &duration.to_string(),
+                "-map", "0:v:0",
+                "-an",
+            ]);
+
+            if *copy {
+                // The MPEG-TS muxer converts the copied stream to Annex B
+                cmd.args(["-c:v", "copy", "-avoid_negative_ts", "make_zero"]);
+            } else {
+                cmd.args(&encoder_args);
+            }
+
+            cmd.args(["-f", "mpegts", "-y", piece_path.to_str().unwrap()]);
+
+            // Report progress relative to the whole segment
+            run_cut(cmd, piece_path, *duration, cancel, |progress: &Progress| {
+                let out_time = offset + progress.out_time;
+                on_progress(&Progress {
+                    out_time,
+                    percent: (out_time / cut.duration * 100.0).clamp(0.0, 100.0),
+                    ..progress.clone()
+                });
+            })
+            .await?;
+
+            offset += duration;
+        }
+
+        std::fs::write(&list_path, merger::concat_list(&piece_paths))?;
+
+        // Join the video pieces and take the audio from the source in one pass
+        let mut cmd = ffmpeg_command()?;
+
+        cmd.args(PROGRESS_ARGS).args([
+            "-f", "concat",
+            "-safe", "0",
+            "-i", list_path.to_str().unwrap(),
+            "-ss", &cut.start_time.to_string(),
+            "-t", &cut.duration.to_string(),
+            "-i", cut.input_path,
+            "-map", "0:v:0",
+            "-map", "1:a?",
             "-c:v", "copy",
-            "-c:a", "copy",
-            "-avoid_negative_ts", "make_zero",
-            "-y",
-            output_path.to_str().unwrap(),
+            "-c:a", "aac",
+            "-b:a", "192k",
+        ]);
+
+        if let Some(timescale) = parameters.timescale {
+            cmd.args(["-video_track_timescale", &timescale.to_string()]);
+        }
+
+        cmd.args(["-y", cut.output_path.to_str().unwrap()]);
+
+        run_cut(cmd, cut.output_path, cut.duration, cancel, |_: &Progress| {}).await
+    }
+    .await;
+
+    for path in piece_paths.iter().chain([&list_path]) {
+        let _ = std::fs::remove_file(path);
+    }
+
+    result
+}
+
+/// Parameters of the first video stream a re-encode has to reproduce
+struct StreamParameters {
+    codec: String,
+    profile: Option<String>,
+    level: Option<i64>,
+    pixel_format: Option<String>,
+    timescale: Option<u64>, // denominator of the stream's time base
+}
+
+async fn stream_parameters(video_path: &str) -> Result<StreamParameters> {
+    let ffprobe_path = get_ffprobe_path();
+
+    let output = Command::from(new_command(&ffprobe_path))
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-show_entries", "stream=codec_name,profile,level,pix_fmt,time_base",
+            "-of", "json",
+            video_path,
         ])
         .stdin(Stdio::null())
-        .stdout(Stdio::piped())  // Capture FFmpeg output for debugging
+        .stdout(Stdio::piped())
         .stderr(Stdio::piped())
-        .output()?;  // Run command and capture output
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::spawn(&ffprobe_path, e))?;
+
+    if !output.status.success() {
+        return Err(Error::probe(video_path, &output).into());
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout)?;
+    let stream = &json["streams"][0];
+
+    Ok(StreamParameters {
+        codec: string_field(&stream["codec_name"]).unwrap_or_default(),
+        profile: string_field(&stream["profile"]),
+        level: stream["level"].as_i64().filter(|&level| level > 0),
+        pixel_format: string_field(&stream["pix_fmt"]),
+        timescale: stream["time_base"]
+            .as_str()
+            .and_then(|base| base.split_once('/'))
+            .and_then(|(_, den)| den.parse().ok()),
+    })
+}
+
+/// Encoder arguments producing the source's codec, profile, level and pixel
+/// format, or `None` if they can't be matched
+fn matching_encoder_args(parameters: &StreamParameters, settings: &CompressionSettings) -> Option<Vec<String>> {
+    let profile = parameters.profile.as_deref()?;
+    let level = parameters.level?;
+    let pixel_format = parameters.pixel_format.clone()?;
+
+    let mut args: Vec<String> = match parameters.codec.as_str() {
+        "h264" => {
+            let profile = match profile {
+                "Baseline" | "Constrained Baseline" => "baseline",
+                "Main" => "main",
+                "High" => "high",
+                "High 10" => "high10",
+                "High 4:2:2" => "high422",
+                "High 4:4:4 Predictive" => "high444",
+                _ => return None,
+            };
+            // FFprobe reports H.264 levels times ten, e.g. 41 for 4.1
+            vec![
+                "-c:v".into(), "libx264".into(),
+                "-profile:v".into(), profile.into(),
+                "-level:v".into(), format!("{}.{}", level / 10, level % 10),
+            ]
+        }
+        "hevc" => {
+            let profile = match profile {
+                "Main" => "main",
+                "Main 10" => "main10",
+                _ => return None,
+            };
+            // HEVC levels are reported times thirty, e.g. 123 for 4.1.
+            // Repeated headers put the VPS/SPS/PPS in-band on every keyframe.
+            vec![
+                "-c:v".into(), "libx265".into(),
+                "-profile:v".into(), profile.into(),
+                "-x265-params".into(), format!("level-idc={:.1}:repeat-headers=1", level as f64 / 30.0),
+            ]
+        }
+        _ => return None,
+    };
+
+    args.extend([
+        "-pix_fmt".into(), pixel_format,
+        "-preset".into(), settings.preset().into(),
+        "-crf".into(), settings.quality().to_string(),
+    ]);
+
+    Some(args)
+}
+
+async fn run_cut(
+    cmd: Command,
+    output_path: &Path,
+    duration: f64,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    // Run command and capture output, killing FFmpeg if the job is cancelled
+    let output = process::run_with_progress(cmd, output_path, cancel, duration, on_progress).await?;
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow!("FFmpeg failed to cut segment: {}", stderr));
+        return Err(Error::ffmpeg("FFmpeg failed to cut segment", &output).into());
+    }
+
+    Ok(())
+}
+
+/// How far before a time to look for the keyframe preceding it. Longer
+/// GOPs are rare, the requested time is used if none is found.
+const KEYFRAME_LOOKBEHIND: f64 = 10.0;
+
+/// Keyframe timestamps of the first video stream between `start` and `end`.
+/// Only packets in that range are read, not the whole file.
+pub async fn keyframes(video_path: &str, start: f64, end: f64, cancel: &CancellationToken) -> Result<Vec<f64>> {
+    let intervals = format!("{}%{}", (start - TIME_EPSILON).max(0.0), end + TIME_EPSILON);
+
+    Ok(probe_keyframes(video_path, Some(&intervals), cancel)
+        .await?
+        .into_iter()
+        .filter(|&t| t >= start - TIME_EPSILON && t <= end + TIME_EPSILON)
+        .collect())
+}
+
+/// The last keyframe at or before `time`, found by reading only the
+/// packets shortly before it
+async fn keyframe_before(video_path: &str, time: f64, cancel: &CancellationToken) -> Result<Option<f64>> {
+    let from = (time - KEYFRAME_LOOKBEHIND).max(0.0);
+    let intervals = format!("{}%{}", from, time + TIME_EPSILON);
+
+    Ok(probe_keyframes(video_path, Some(&intervals), cancel)
+        .await?
+        .into_iter()
+        .rev()
+        .find(|&t| t <= time + TIME_EPSILON))
+}
+
+/// Keyframe index of a file, tagged with the file state it was built from
+struct CachedKeyframes {
+    modified: Option<SystemTime>,
+    len: u64,
+    keyframes: Arc<Vec<f64>>,
+}
+
+fn keyframe_cache() -> &'static Mutex<HashMap<PathBuf, CachedKeyframes>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedKeyframes>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// All keyframe timestamps of the first video stream, sorted.
+///
+/// Scanning packets reads the whole file, so the result is cached until the
+/// file's size or modification time changes. FFprobe runs as an async child
+/// so a long scan doesn't hold up a runtime worker.
+pub async fn keyframe_index(video_path: &str) -> Result<Arc<Vec<f64>>> {
+    let key = std::fs::canonicalize(video_path)?;
+    let file_info = std::fs::metadata(&key)?;
+    let modified = file_info.modified().ok();
+    let len = file_info.len();
+
+    if let Some(cached) = keyframe_cache().lock().unwrap().get(&key) {
+        if cached.modified == modified && cached.len == len {
+            return Ok(cached.keyframes.clone());
+        }
     }
 
-    Ok(output_path.to_str().unwrap().to_string())
+    let keyframes = Arc::new(probe_keyframes(video_path, None, &CancellationToken::new()).await?);
+    keyframe_cache().lock().unwrap().insert(
+        key,
+        CachedKeyframes {
+            modified,
+            len,
+            keyframes: keyframes.clone(),
+        },
+    );
+
+    Ok(keyframes)
 }
 
+/// Sorted keyframe timestamps of the first video stream, limited to FFprobe
+/// `-read_intervals` if given. FFprobe is killed if `cancel` fires.
+async fn probe_keyframes(video_path: &str, intervals: Option<&str>, cancel: &CancellationToken) -> Result<Vec<f64>> {
+    let ffprobe_path = get_ffprobe_path();
+
+    let mut cmd = Command::from(new_command(&ffprobe_path));
+    cmd.args(["-v", "error", "-select_streams", "v:0"]);
+    if let Some(intervals) = intervals {
+        cmd.args(["-read_intervals", intervals]);
+    }
+    cmd.args([
+        "-show_entries", "packet=pts_time,flags",
+        "-of", "csv=print_section=0",
+        video_path,
+    ])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true);
+
+    let output = tokio::select! {
+        output = cmd.output() => output.map_err(|e| Error::spawn(&ffprobe_path, e))?,
+        _ = cancel.cancelled() => return Err(process::Cancelled.into()),
+    };
+
+    if !output.status.success() {
+        return Err(Error::probe(video_path, &output).into());
+    }
+
+    let mut keyframes: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pts_time, flags) = line.split_once(',')?;
+            if !flags.contains('K') {
+                return None;
+            }
+            pts_time.parse::<f64>().ok()
+        })
+        .collect();
+
+    keyframes.sort_by(f64::total_cmp);
+    keyframes.dedup();
+
+    Ok(keyframes)
+}
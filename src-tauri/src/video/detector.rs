@@ -0,0 +1,330 @@
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use super::process::{self, Progress};
+use crate::commands::analysis::{SceneCut, TimeRange};
+use crate::error::Error;
+use crate::utils::ffmpeg_command;
+
+/// Find shot changes where FFmpeg's scene score exceeds `threshold` (0-1).
+///
+/// Cuts closer than `min_length` seconds to the previous one are skipped.
+/// `on_cut` is called for every cut as FFmpeg finds it.
+pub async fn detect_scenes(
+    video_path: &str,
+    threshold: f64,
+    min_length: f64,
+    mut on_cut: impl FnMut(&SceneCut) + Send,
+) -> Result<Vec<SceneCut>> {
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args([
+        "-v", "error",
+        "-i", video_path,
+        "-an",
+        "-sn",
+        "-map", "0:v:0",
+        // Print the time and score of each selected frame to stdout
+        "-vf", &format!("select='gt(scene,{})',metadata=print:file=pipe\\:1", threshold.clamp(0.0, 1.0)),
+        "-f", "null",
+        "-",
+    ]);
+
+    let mut cuts: Vec<SceneCut> = Vec::new();
+    let mut lines = LineBuffer::default();
+    let mut time = None;
+
+    let output = process::run_streaming(cmd, &CancellationToken::new(), |chunk| {
+        for line in lines.push(chunk) {
+            // Each frame is a "frame:N pts:P pts_time:T" line followed by its metadata
+            if let Some(pts_time) = line.split("pts_time:").nth(1) {
+                time = pts_time.split_whitespace().next().and_then(|t| t.parse::<f64>().ok());
+            } else if let Some(score) = line.strip_prefix("lavfi.scene_score=") {
+                let (Some(time), Ok(score)) = (time.take(), score.trim().parse::<f64>()) else {
+                    continue;
+                };
+
+                let previous = cuts.last().map_or(0.0, |cut| cut.time);
+                if time - previous < min_length {
+                    continue;
+                }
+
+                let cut = SceneCut { time, score };
+                on_cut(&cut);
+                cuts.push(cut);
+            }
+        }
+    })
+    .await?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("FFmpeg failed to detect scenes", &output).into());
+    }
+
+    Ok(cuts)
+}
+
+/// Seconds of black or frozen frames at a segment edge that are ignored,
+/// so a single dark frame doesn't move the boundary
+const EDGE_TOLERANCE: f64 = 0.05;
+
+/// Seconds at each end of a segment scanned for dead frames. Only the edges
+/// are trimmed, so the middle of a long segment is never decoded.
+const EDGE_WINDOW: f64 = 10.0;
+
+/// Shortest segment left after trimming its edges, anything shorter is
+/// treated as all black and left alone
+const MIN_TRIMMED_LENGTH: f64 = 0.5;
+
+/// Find audio quieter than `noise_db` for at least `min_duration` seconds
+/// in the first audio stream. `duration` is only used for progress.
+pub async fn detect_silence(
+    video_path: &str,
+    noise_db: f64,
+    min_duration: f64,
+    duration: f64,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<Vec<TimeRange>> {
+    let detection = RangeDetection {
+        stream: "0:a:0",
+        filter_option: "-af",
+        filter: format!(
+            "silencedetect=noise={}dB:duration={},ametadata=print:file=pipe\\:1",
+            noise_db,
+            min_duration.max(0.01)
+        ),
+        markers: &[SILENCE_MARKERS],
+        context: "FFmpeg failed to detect silence",
+    };
+
+    detection
+        .run(video_path, &TimeRange { start: 0.0, end: duration }, cancel, on_progress)
+        .await
+}
+
+/// Find black frames within `window` lasting at least `min_duration` seconds.
+/// A pixel is black below `pixel_threshold` (0-1) of the luma range.
+pub async fn detect_black(
+    video_path: &str,
+    window: &TimeRange,
+    min_duration: f64,
+    pixel_threshold: f64,
+    cancel: &CancellationToken,
+) -> Result<Vec<TimeRange>> {
+    let detection = RangeDetection {
+        stream: "0:v:0",
+        filter_option: "-vf",
+        filter: format!("{},metadata=print:file=pipe\\:1", black_filter(min_duration, pixel_threshold)),
+        markers: &[BLACK_MARKERS],
+        context: "FFmpeg failed to detect black frames",
+    };
+
+    detection.run(video_path, window, cancel, |_| {}).await
+}
+
+/// Find frozen frames within `window` lasting at least `min_duration`
+/// seconds. Frames differing by less than `noise_db` count as the same.
+pub async fn detect_freeze(
+    video_path: &str,
+    window: &TimeRange,
+    noise_db: f64,
+    min_duration: f64,
+    cancel: &CancellationToken,
+) -> Result<Vec<TimeRange>> {
+    let detection = RangeDetection {
+        stream: "0:v:0",
+        filter_option: "-vf",
+        filter: format!("{},metadata=print:file=pipe\\:1", freeze_filter(noise_db, min_duration)),
+        markers: &[FREEZE_MARKERS],
+        context: "FFmpeg failed to detect frozen frames",
+    };
+
+    detection.run(video_path, window, cancel, |_| {}).await
+}
+
+/// Narrow `start`..`end` to exclude black or frozen frames at either edge,
+/// by at most `EDGE_WINDOW` seconds on each side
+pub async fn trim_dead_edges(
+    video_path: &str,
+    start: f64,
+    end: f64,
+    cancel: &CancellationToken,
+) -> Result<(f64, f64)> {
+    // Both filters share one decode of the window
+    let detection = RangeDetection {
+        stream: "0:v:0",
+        filter_option: "-vf",
+        filter: format!(
+            "{},{},metadata=print:file=pipe\\:1",
+            black_filter(0.1, 0.1),
+            freeze_filter(-60.0, 0.5)
+        ),
+        markers: &[BLACK_MARKERS, FREEZE_MARKERS],
+        context: "FFmpeg failed to detect black or frozen frames",
+    };
+
+    let windows = if end - start <= 2.0 * EDGE_WINDOW {
+        vec![TimeRange { start, end }]
+    } else {
+        vec![
+            TimeRange { start, end: start + EDGE_WINDOW },
+            TimeRange { start: end - EDGE_WINDOW, end },
+        ]
+    };
+
+    let mut ranges = Vec::new();
+    for window in &windows {
+        ranges.extend(detection.run(video_path, window, cancel, |_| {}).await?);
+    }
+    ranges.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    // Ranges may overlap, e.g. a black screen is also frozen
+    let mut trimmed_start = start;
+    for range in &ranges {
+        if range.start <= trimmed_start + EDGE_TOLERANCE {
+            trimmed_start = trimmed_start.max(range.end);
+        }
+    }
+
+    let mut trimmed_end = end;
+    for range in ranges.iter().rev() {
+        if range.end >= trimmed_end - EDGE_TOLERANCE {
+            trimmed_end = trimmed_end.min(range.start);
+        }
+    }
+
+    if trimmed_end - trimmed_start < MIN_TRIMMED_LENGTH {
+        return Ok((start, end));
+    }
+
+    Ok((trimmed_start, trimmed_end))
+}
+
+fn black_filter(min_duration: f64, pixel_threshold: f64) -> String {
+    format!("blackdetect=d={}:pix_th={}", min_duration.max(0.01), pixel_threshold.clamp(0.0, 1.0))
+}
+
+fn freeze_filter(noise_db: f64, min_duration: f64) -> String {
+    format!("freezedetect=n={}dB:d={}", noise_db, min_duration.max(0.01))
+}
+
+/// Frame metadata keys marking the start and end of a detected range
+type Markers = (&'static str, &'static str);
+
+const SILENCE_MARKERS: Markers = ("lavfi.silence_start", "lavfi.silence_end");
+const BLACK_MARKERS: Markers = ("lavfi.black_start", "lavfi.black_end");
+const FREEZE_MARKERS: Markers = ("lavfi.freezedetect.freeze_start", "lavfi.freezedetect.freeze_end");
+
+/// FFmpeg detection filters that mark the start and end of ranges with
+/// frame metadata
+struct RangeDetection {
+    stream: &'static str,
+    filter_option: &'static str,
+    filter: String,
+    markers: &'static [Markers], // one pair per filter in the chain
+    context: &'static str,
+}
+
+impl RangeDetection {
+    /// Run the filter over `window` of the file, returning ranges in source time
+    async fn run(
+        &self,
+        video_path: &str,
+        window: &TimeRange,
+        cancel: &CancellationToken,
+        mut on_progress: impl FnMut(&Progress) + Send,
+    ) -> Result<Vec<TimeRange>> {
+        let mut cmd = ffmpeg_command()?;
+        let duration = window.end - window.start;
+
+        cmd.args([
+            "-v", "error",
+            "-ss", &window.start.max(0.0).to_string(),
+            "-t", &duration.to_string(),
+            "-i", video_path,
+            "-map", self.stream,
+            // Print the range boundaries to stdout as they are found
+            self.filter_option, &self.filter,
+            "-f", "null",
+            "-",
+        ]);
+
+        let prefixes: Vec<(String, String)> = self
+            .markers
+            .iter()
+            .map(|(start_key, end_key)| (format!("{}=", start_key), format!("{}=", end_key)))
+            .collect();
+        let mut ranges = Vec::new();
+        let mut lines = LineBuffer::default();
+        // Open range start of each filter, they overlap independently
+        let mut starts: Vec<Option<f64>> = vec![None; prefixes.len()];
+
+        let output = process::run_streaming(cmd, cancel, |chunk| {
+            for line in lines.push(chunk) {
+                // Times are relative to the seek point
+                if let Some(pts_time) = line.split("pts_time:").nth(1) {
+                    if let Some(time) = pts_time.split_whitespace().next().and_then(|t| t.parse::<f64>().ok()) {
+                        on_progress(&Progress {
+                            out_time: time,
+                            percent: if duration > 0.0 { (time / duration * 100.0).clamp(0.0, 100.0) } else { 0.0 },
+                            ..Default::default()
+                        });
+                    }
+                    continue;
+                }
+
+                for ((start_prefix, end_prefix), start) in prefixes.iter().zip(starts.iter_mut()) {
+                    if let Some(value) = line.strip_prefix(start_prefix.as_str()) {
+                        *start = value.trim().parse::<f64>().ok();
+                    } else if let Some(value) = line.strip_prefix(end_prefix.as_str()) {
+                        if let (Some(start), Ok(end)) = (start.take(), value.trim().parse::<f64>()) {
+                            ranges.push(TimeRange {
+                                start: window.start + start.max(0.0),
+                                end: window.start + end,
+                            });
+                        }
+                    }
+                }
+            }
+        })
+        .await?;
+
+        if !output.status.success() {
+            return Err(Error::ffmpeg(self.context, &output).into());
+        }
+
+        // A range running to the end of the window has no end marker
+        for start in starts.into_iter().flatten() {
+            if duration > start {
+                ranges.push(TimeRange {
+                    start: window.start + start.max(0.0),
+                    end: window.end,
+                });
+            }
+        }
+
+        Ok(ranges)
+    }
+}
+
+/// Splits streamed output into complete lines
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+
+        let complete: Vec<u8> = self.pending.drain(..=end).collect();
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+}
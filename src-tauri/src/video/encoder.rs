@@ -1,42 +1,121 @@
-use std::os::windows::process::CommandExt;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use anyhow::{Result, anyhow};
-use uuid::Uuid;
-use super::super::commands::video::CompressionSettings;
-use crate::utils::get_ffmpeg_path; 
-
-pub fn compress_video(input_path: &str, output_dir: &str, settings: CompressionSettings) -> Result<String> {
-    let ffmpeg_path = get_ffmpeg_path();  
-    
-    if !ffmpeg_path.exists() {
-        return Err(anyhow!("FFmpeg not found at {:?}", ffmpeg_path));
+use std::path::Path;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use super::{cutter, process::{self, Progress, PROGRESS_ARGS}};
+use super::super::commands::video::{Anchor, CompressionSettings, OverlaySettings, VideoMetadata};
+use crate::error::Error;
+use crate::utils::{ffmpeg_command, fill_reserved_output, unique_output_path};
+
+pub async fn compress_video(
+    input_path: &str,
+    output_dir: &str,
+    settings: &CompressionSettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<String> {
+    // Extract segment name from input_path
+    let input_filename = Path::new(input_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let base_output_name = format!("{}_compressed", input_filename);
+    let mut output_dir_path = Path::new(output_dir);
+
+    // Ensure output_dir is actually a folder, not a file
+    if output_dir_path.extension().is_some() {
+        output_dir_path = output_dir_path.parent().unwrap_or(Path::new("."));
     }
 
-    let output_filename = format!("compressed_{}.mp4", Uuid::new_v4());
-    let output_path = Path::new(output_dir).join(&output_filename);
+    settings.check_overlay()?;
+
+    // Ensure unique filename by appending a number if needed
+    let final_output_path = unique_output_path(output_dir_path, &base_output_name)?;
+
+    fill_reserved_output(&final_output_path, async {
+        // Duration is only used for the percentage, so a failed probe isn't fatal
+        // unless the overlay needs the frame size
+        let metadata = cutter::get_metadata(input_path).await;
+        let duration = metadata.as_ref().map(|m| m.duration).unwrap_or_default();
+
+        let mut command = ffmpeg_command()?;
+
+        command.args(PROGRESS_ARGS).args(["-i", input_path]);
+
+        if let Some(overlay) = settings.overlay() {
+            command.args([
+                "-i", &overlay.image_path,
+                "-filter_complex", &overlay_filter(overlay, &metadata?),
+                "-map", "[v]",
+                "-map", "0:a?",
+            ]);
+        }
+
+        command.args(video_codec_args(settings));
+
+        command.args([
+            "-c:a", "aac",
+            "-b:a", "128k",
+            "-y",
+            final_output_path.to_str().unwrap()
+        ]);
+
+        let output = process::run_with_progress(command, &final_output_path, cancel, duration, on_progress).await?;
+
+        if !output.status.success() {
+            return Err(Error::ffmpeg("FFmpeg compression failed", &output).into());
+        }
 
-    #[cfg(target_os = "windows")]
-    let mut command = {
-        let mut cmd = Command::new(&ffmpeg_path);
-        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW flag (prevents CMD window)
-        cmd
+        Ok(())
+    })
+    .await?;
+
+    Ok(final_output_path.to_str().unwrap().to_string())
+}
+
+/// Filter drawing input 1 over input 0 as `[v]`
+fn overlay_filter(overlay: &OverlaySettings, metadata: &VideoMetadata) -> String {
+    // Filters see the frame after rotation
+    let video_width = if metadata.rotation % 180 == 90 { metadata.height } else { metadata.width };
+    let width = ((f64::from(video_width) * overlay.scale.clamp(0.01, 1.0)).round() as u32).max(1);
+
+    let (mx, my) = (overlay.margin_x, overlay.margin_y);
+    let (x, y) = match overlay.anchor {
+        Anchor::TopLeft => (mx.to_string(), my.to_string()),
+        Anchor::TopRight => (format!("main_w-overlay_w-{}", mx), my.to_string()),
+        Anchor::BottomLeft => (mx.to_string(), format!("main_h-overlay_h-{}", my)),
+        Anchor::BottomRight => (format!("main_w-overlay_w-{}", mx), format!("main_h-overlay_h-{}", my)),
+        Anchor::Center => ("(main_w-overlay_w)/2".to_string(), "(main_h-overlay_h)/2".to_string()),
     };
 
-    #[cfg(not(target_os = "windows"))]
-    let mut command = Command::new(&ffmpeg_path);
+    let enable = match (overlay.start, overlay.end) {
+        (Some(start), Some(end)) => format!(":enable='between(t,{},{})'", start, end),
+        (Some(start), None) => format!(":enable='gte(t,{})'", start),
+        (None, Some(end)) => format!(":enable='lte(t,{})'", end),
+        (None, None) => String::new(),
+    };
+
+    format!(
+        "[1:v]scale={}:-1,format=rgba,colorchannelmixer=aa={}[logo];[0:v][logo]overlay=x={}:y={}{}[v]",
+        width,
+        overlay.opacity.clamp(0.0, 1.0),
+        x,
+        y,
+        enable
+    )
+}
+
+/// Video encoder arguments for the given settings
+pub fn video_codec_args(settings: &CompressionSettings) -> Vec<String> {
+    let mut args = vec![
+        "-c:v".to_string(), settings.codec().to_string(),
+        "-preset".to_string(), settings.preset().to_string(),
+    ];
 
-    command.args(&[
-        "-i", input_path,
-        "-c:v", &settings.codec(),
-        "-preset", &settings.preset(),
-    ]);
-    
     // CRF mode for x264/x265, bitrate for others
     if settings.codec() == "libx264" || settings.codec() == "libx265" {
-        command.args(&[
-            "-crf", &settings.quality().to_string(),
-        ]);
+        args.extend(["-crf".to_string(), settings.quality().to_string()]);
     } else {
         let bitrate = match settings.quality() {
             0..=10 => "8M",
@@ -44,24 +123,8 @@ pub fn compress_video(input_path: &str, output_dir: &str, settings: CompressionS
             21..=30 => "2M",
             _ => "1M",
         };
-        command.args(&["-b:v", bitrate]);
-    }
-
-    command.args(&[
-        "-c:a", "aac",
-        "-b:a", "128k",
-        "-y",
-        output_path.to_str().unwrap()
-    ])
-    .stdin(Stdio::null())
-    .stdout(Stdio::null())  // Hide standard output
-    .stderr(Stdio::null()); // Hide error output
-
-    let status = command.status()?;
-
-    if !status.success() {
-        return Err(anyhow!("FFmpeg compression failed for {}", input_path));
+        args.extend(["-b:v".to_string(), bitrate.to_string()]);
     }
 
-    Ok(output_path.to_str().unwrap().to_string())
+    args
 }
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use super::process;
+use crate::commands::media::ImageFormat;
+use crate::error::Error;
+use crate::utils::{cache_dir, file_fingerprint, ffmpeg_command};
+
+/// Grab the frame at `time` seconds as an image, `width` keeps the aspect
+/// ratio. Frames are cached on disk by file, time, width and format.
+pub async fn extract_frame(
+    video_path: &str,
+    time: f64,
+    width: Option<u32>,
+    format: ImageFormat,
+) -> Result<PathBuf> {
+    let time = time.max(0.0);
+
+    // Millisecond precision is finer than any frame rate we care about
+    let output_path = cache_dir("frames")?.join(format!(
+        "{}_{}_{}.{}",
+        file_fingerprint(video_path)?,
+        (time * 1000.0).round() as u64,
+        width.unwrap_or(0),
+        format.extension(),
+    ));
+
+    if output_path.is_file() {
+        return Ok(output_path);
+    }
+
+    // Rename into place once written so readers never see a partial image.
+    // The name is unique as the timeline may request the same frame twice at once.
+    let partial_path = output_path.with_extension(format!("{}.{}", Uuid::new_v4().simple(), format.extension()));
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args([
+        "-v", "error",
+        "-ss", &time.to_string(), // Input seeking jumps to the nearest keyframe first, which is fast
+        "-i", video_path,
+        "-frames:v", "1",
+        "-an",
+    ]);
+
+    if let Some(width) = width {
+        cmd.args(["-vf", &format!("scale={}:-2", width)]);
+    }
+
+    cmd.args(format.codec_args()).args(["-update", "1", "-y", partial_path.to_str().unwrap()]);
+
+    let output = process::run(cmd, &partial_path, &CancellationToken::new()).await?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&partial_path);
+        return Err(Error::ffmpeg("FFmpeg failed to extract the frame", &output).into());
+    }
+
+    // FFmpeg exits cleanly without writing anything when `time` is past the end
+    if !partial_path.is_file() {
+        return Err(Error::InvalidRequest {
+            message: format!("No frame at {} seconds", time),
+        }
+        .into());
+    }
+
+    std::fs::rename(&partial_path, &output_path)?;
+
+    Ok(output_path)
+}
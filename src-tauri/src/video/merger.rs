@@ -1,99 +1,34 @@
-use tauri::{Emitter, Manager};
-use std::os::windows::process::CommandExt;
-use std::process::{Command, Stdio};
 use std::path::Path;
-use std::fs::File;
-use std::io::{BufReader, BufRead};
 use uuid::Uuid;
-use anyhow::{Result, anyhow};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use crate::commands::video::CompressionSettings;
-use crate::utils::get_ffmpeg_path;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use super::{cutter, encoder};
+use super::process::{self, Progress, PROGRESS_ARGS};
+use crate::commands::analysis::TimeRange;
+use crate::commands::video::{CompressionSettings, Transition, TransitionKind, VideoMetadata};
+use crate::error::Error;
+use crate::utils::{ffmpeg_command, fill_reserved_output, unique_output_path};
 
 pub async fn add_intro_with_progress(
     intro_path: String, 
     video_path: String, 
     output_dir: String,
     settings: Option<CompressionSettings>,
-    window: tauri::Window
-) -> Result<String, String> {
-    let temp_dir = std::env::temp_dir();
-    let progress_file = temp_dir.join(format!("ffmpeg_progress_{}.txt", Uuid::new_v4()));
-    let progress_path = progress_file.to_str().unwrap().to_string();
-    
-    let intro_duration = match crate::video::cutter::get_metadata(&intro_path) {
-        Ok(meta) => meta.duration,
-        Err(e) => return Err(format!("Failed to get intro metadata: {}", e))
-    };
-    
-    let video_duration = match crate::video::cutter::get_metadata(&video_path) {
-        Ok(meta) => meta.duration,
-        Err(e) => return Err(format!("Failed to get video metadata: {}", e))
-    };
-    
-    let total_duration = intro_duration + video_duration;
-    
-    let progress_path_clone = progress_path.clone();
-    let window_clone = window.clone();
-    
-    let _monitor_handle = thread::spawn(move || {
-        let path = progress_path_clone;
-        
-        while !Path::new(&path).exists() {
-            thread::sleep(std::time::Duration::from_millis(100));
-        }
-        
-        loop {
-            if !Path::new(&path).exists() {
-                break;
-            }
-            
-            if let Ok(file) = File::open(&path) {
-                let reader = BufReader::new(file);
-                let mut current_time = 0.0;
-                
-                for line in reader.lines().flatten() {
-                    if line.starts_with("out_time_ms=") {
-                        if let Ok(time_ms) = line.trim_start_matches("out_time_ms=").parse::<f64>() {
-                            current_time = time_ms / 1_000_000.0;
-                        }
-                    }
-                }
-                
-                let progress = ((current_time / total_duration) * 100.0).min(100.0).max(0.0);
-                
-                let _ = window_clone.emit("intro_progress", progress);
-            }
-            
-            thread::sleep(std::time::Duration::from_millis(200));
-        }
-        
-        let _ = window_clone.emit("intro_progress", 100.0);
-    });
-
-    let result = add_intro_internal(&intro_path, &video_path, &output_dir, settings, &progress_path).await;
-    let _ = std::fs::remove_file(progress_file);
-
-    result.map_err(|e| format!("Failed to add intro: {}", e))
+    transition: &Transition,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<String> {
+    merge_clips(&[intro_path, video_path], &output_dir, settings, transition, cancel, on_progress).await
 }
 
-
-
-async fn add_intro_internal(
-    intro_path: &str, 
-    video_path: &str, 
+pub async fn add_intro(
+    intro_path: &str,
+    video_path: &str,
     output_dir: &str,
-    settings: Option<CompressionSettings>,
-    progress_file: &str
+    transition: &Transition,
+    cancel: &CancellationToken,
 ) -> Result<String> {
-    let ffmpeg_path = get_ffmpeg_path();
-    
-    if !ffmpeg_path.exists() {
-        return Err(anyhow!("FFmpeg not found at {:?}", ffmpeg_path));
-    }
-    
-    // Extract filenames without extensions for better naming
+    // Extract filenames (without extensions) for better naming
     let intro_filename = Path::new(intro_path)
         .file_stem()
         .unwrap_or_default()
@@ -103,158 +38,404 @@ async fn add_intro_internal(
         .file_stem()
         .unwrap_or_default()
         .to_string_lossy();
-    
-    // Standardized output file naming
-    let mut base_output_name = format!("{}_{}", intro_filename, video_filename);
-    let mut output_path = Path::new(output_dir).join(format!("{}.mp4", base_output_name));
-    
-    // Ensure unique naming if the file already exists
-    let mut count = 1;
-    while output_path.exists() {
-        base_output_name = format!("{}_{}_{}", intro_filename, video_filename, count);
-        output_path = Path::new(output_dir).join(format!("{}.mp4", base_output_name));
-        count += 1;
+
+    // Generate a unique output filename with UUID
+    let unique_id = Uuid::new_v4();
+    let output_filename = format!("merged_{}_{}_{}.mp4", intro_filename, video_filename, unique_id);
+    let output_path = Path::new(output_dir).join(output_filename);
+
+    let inputs = [intro_path.to_string(), video_path.to_string()];
+    join_clips(&inputs, &output_path, &CompressionSettings::for_merging(), transition, cancel, |_| {}).await?;
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+/// Join `inputs` in order into `<output_dir>/<their names joined by _>.mp4`.
+/// `settings` are only used if the clips can't be joined losslessly.
+pub async fn merge_clips(
+    inputs: &[String],
+    output_dir: &str,
+    settings: Option<CompressionSettings>,
+    transition: &Transition,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<String> {
+    let name = inputs
+        .iter()
+        .map(|input| Path::new(input).file_stem().unwrap_or_default().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    let output_path = unique_output_path(output_dir, &name)?;
+    let settings = settings.unwrap_or_else(CompressionSettings::for_merging);
+
+    fill_reserved_output(&output_path, join_clips(inputs, &output_path, &settings, transition, cancel, on_progress)).await?;
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
+
+/// Contents of a concat demuxer list for `paths`
+pub fn concat_list<P: AsRef<Path>>(paths: &[P]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("file '{}'\n", path.as_ref().to_string_lossy().replace('\'', "'\\''")))
+        .collect()
+}
+
+/// Join files with identical codec parameters without re-encoding
+pub async fn concat_files(
+    inputs: &[String],
+    output_path: &Path,
+    duration: f64,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let list_path = output_path.with_extension("concat.txt");
+    std::fs::write(&list_path, concat_list(inputs))?;
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args(PROGRESS_ARGS).args([
+        "-f", "concat",
+        "-safe", "0",
+        "-i", list_path.to_str().unwrap(),
+        "-map", "0",
+        "-c", "copy",
+        "-movflags", "+faststart",
+        "-y",
+        output_path.to_str().unwrap(),
+    ]);
+
+    let result = process::run_with_progress(cmd, output_path, cancel, duration, on_progress).await;
+    let _ = std::fs::remove_file(&list_path);
+    let output = result?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("FFmpeg failed to join the segments", &output).into());
     }
-    
-    // Create command with hidden window
-    #[cfg(target_os = "windows")]
-    let mut cmd = {
-        let mut command = Command::new(&ffmpeg_path);
-        command.creation_flags(0x08000000); // CREATE_NO_WINDOW flag
-        command
+
+    Ok(())
+}
+
+/// Join `inputs` in order into `output_path`, losslessly when their streams
+/// share the same format and there is no transition, and by re-encoding with
+/// `settings` otherwise
+pub async fn join_clips(
+    inputs: &[String],
+    output_path: &Path,
+    settings: &CompressionSettings,
+    transition: &Transition,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let mut metadata = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        metadata.push(cutter::get_metadata(input).await?);
+    }
+
+    let duration = metadata.iter().map(|m| m.duration).sum();
+
+    if transition.is_cut() && metadata.windows(2).all(|pair| same_format(&pair[0], &pair[1])) {
+        match concat_files(inputs, output_path, duration, cancel, &mut on_progress).await {
+            Err(e) if process::is_cancelled(&e) => return Err(e),
+            // Matching formats can still fail to copy, e.g. on broken timestamps
+            Err(_) => {}
+            Ok(()) => return Ok(()),
+        }
+    }
+
+    concat_reencode(inputs, &metadata, output_path, settings, transition, cancel, on_progress).await
+}
+
+/// Whether the concat demuxer can join the two files without re-encoding
+fn same_format(a: &VideoMetadata, b: &VideoMetadata) -> bool {
+    let audio = |m: &VideoMetadata| {
+        m.audio_streams
+            .first()
+            .map(|stream| (stream.codec.clone(), stream.channels, stream.sample_rate))
     };
-    
-    #[cfg(not(target_os = "windows"))]
-    let mut cmd = Command::new(&ffmpeg_path);
-    
-    // First try with copy codec (faster processing)
-    cmd.args(&[
-        "-i", intro_path,
-        "-i", video_path,
-        "-filter_complex", "[0:v:0][0:a:0][1:v:0][1:a:0] concat=n=2:v=1:a=1 [v][a]",
-        "-map", "[v]",
-        "-map", "[a]",
-        "-c:v", "copy",
-        "-c:a", "aac",
-        "-b:a", "192k",
-        "-progress", progress_file,
+
+    a.codec == b.codec
+        && a.width == b.width
+        && a.height == b.height
+        && a.pixel_format == b.pixel_format
+        && a.sample_aspect_ratio == b.sample_aspect_ratio
+        && (a.framerate - b.framerate).abs() < 0.01
+        && audio(a) == audio(b)
+}
+
+/// Join with the concat filter, or with `xfade` for transitions,
+/// re-encoding everything
+async fn concat_reencode(
+    inputs: &[String],
+    metadata: &[VideoMetadata],
+    output_path: &Path,
+    settings: &CompressionSettings,
+    transition: &Transition,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let format = JoinFormat::for_clips(metadata);
+
+    let mut filter = String::new();
+    for (i, clip) in metadata.iter().enumerate() {
+        filter.push_str(&format.normalize(i, clip));
+    }
+
+    let durations: Vec<f64> = metadata.iter().map(|m| m.duration).collect();
+    let duration = match xfade_name(transition.kind) {
+        Some(name) if inputs.len() > 1 && !transition.is_cut() => {
+            crossfade_filter(&mut filter, name, transition.duration, &durations, format.with_audio)
+        }
+        _ => {
+            for i in 0..inputs.len() {
+                filter.push_str(&format!("[v{}]", i));
+                if format.with_audio {
+                    filter.push_str(&format!("[a{}]", i));
+                }
+            }
+            filter.push_str(&format!("concat=n={}:v=1:a={}[v]", inputs.len(), u8::from(format.with_audio)));
+            if format.with_audio {
+                filter.push_str("[a]");
+            }
+            durations.iter().sum()
+        }
+    };
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args(PROGRESS_ARGS);
+    for input in inputs {
+        cmd.args(["-i", input]);
+    }
+
+    cmd.args(["-filter_complex", &filter, "-map", "[v]"]);
+    if format.with_audio {
+        cmd.args(["-map", "[a]", "-c:a", "aac", "-b:a", "192k"]);
+    }
+
+    cmd.args(encoder::video_codec_args(settings)).args([
+        "-pix_fmt", "yuv420p",
+        "-movflags", "+faststart",
         "-y",
-        output_path.to_str().unwrap()
-    ])
-    .stdin(Stdio::null())
-    .stdout(Stdio::null())
-    .stderr(Stdio::null());
-    
-    let status = cmd.status()?;
-    
-    // If copy codec fails, use re-encoding
-    if !status.success() {
-        let preset = settings.as_ref().map_or("fast", |s| s.preset());
-        let crf = settings.as_ref().map_or(28, |s| s.quality()).to_string();
-        let codec = settings.as_ref().map_or("libx264", |s| s.codec());
-        
-        #[cfg(target_os = "windows")]
-        let mut cmd = {
-            let mut command = Command::new(&ffmpeg_path);
-            command.creation_flags(0x08000000); // CREATE_NO_WINDOW flag
-            command
+        output_path.to_str().unwrap(),
+    ]);
+
+    let output = process::run_with_progress(cmd, output_path, cancel, duration, on_progress).await?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("FFmpeg failed to join the clips", &output).into());
+    }
+
+    Ok(())
+}
+
+/// Render only `ranges` of `input_path`, back to back, into `output_path`.
+///
+/// Everything is trimmed and joined in one filter graph, so audio and video
+/// stay in sync however many ranges there are.
+pub async fn keep_ranges(
+    input_path: &str,
+    ranges: &[TimeRange],
+    output_path: &Path,
+    settings: &CompressionSettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<()> {
+    let with_audio = !cutter::get_metadata(input_path).await?.audio_streams.is_empty();
+    let filter = keep_ranges_filter(ranges, with_audio);
+    let duration = ranges.iter().map(|range| range.end - range.start).sum();
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args(PROGRESS_ARGS).args(["-i", input_path, "-filter_complex", &filter, "-map", "[v]"]);
+    if with_audio {
+        cmd.args(["-map", "[a]", "-c:a", "aac", "-b:a", "192k"]);
+    }
+
+    cmd.args(encoder::video_codec_args(settings)).args([
+        "-pix_fmt", "yuv420p",
+        "-movflags", "+faststart",
+        "-y",
+        output_path.to_str().unwrap(),
+    ]);
+
+    let output = process::run_with_progress(cmd, output_path, cancel, duration, on_progress).await?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("FFmpeg failed to join the ranges", &output).into());
+    }
+
+    Ok(())
+}
+
+/// Split the first video (and audio) stream once per range, trim each copy
+/// and concatenate them into `[v]`/`[a]`
+fn keep_ranges_filter(ranges: &[TimeRange], with_audio: bool) -> String {
+    let count = ranges.len();
+    let labels = |prefix: &str| (0..count).map(|i| format!("[{}{}]", prefix, i)).collect::<String>();
+
+    let mut filter = format!("[0:v:0]split={}{};", count, labels("sv"));
+    if with_audio {
+        filter.push_str(&format!("[0:a:0]asplit={}{};", count, labels("sa")));
+    }
+
+    for (i, range) in ranges.iter().enumerate() {
+        filter.push_str(&format!(
+            "[sv{i}]trim=start={}:end={},setpts=PTS-STARTPTS[v{i}];",
+            range.start, range.end
+        ));
+        if with_audio {
+            filter.push_str(&format!(
+                "[sa{i}]atrim=start={}:end={},asetpts=PTS-STARTPTS[a{i}];",
+                range.start, range.end
+            ));
+        }
+    }
+
+    for i in 0..count {
+        filter.push_str(&format!("[v{}]", i));
+        if with_audio {
+            filter.push_str(&format!("[a{}]", i));
+        }
+    }
+    filter.push_str(&format!("concat=n={}:v=1:a={}[v]", count, u8::from(with_audio)));
+    if with_audio {
+        filter.push_str("[a]");
+    }
+
+    filter
+}
+
+/// Name of the `xfade` transition for `kind`
+fn xfade_name(kind: TransitionKind) -> Option<&'static str> {
+    match kind {
+        TransitionKind::None => None,
+        TransitionKind::Crossfade => Some("fade"),
+        TransitionKind::FadeBlack => Some("fadeblack"),
+        TransitionKind::FadeWhite => Some("fadewhite"),
+        TransitionKind::Wipe => Some("wipeleft"),
+    }
+}
+
+/// Chain `xfade` (and `acrossfade`) over the normalized `[v<i>]`/`[a<i>]`
+/// streams into `[v]`/`[a]`, returning the joined duration
+fn crossfade_filter(filter: &mut String, name: &str, duration: f64, durations: &[f64], with_audio: bool) -> f64 {
+    // A transition can't be longer than half of the shortest clip
+    let shortest = durations.iter().copied().fold(f64::INFINITY, f64::min);
+    let duration = duration.min(shortest / 2.0).max(0.01);
+
+    let last = durations.len() - 1;
+    let mut offset = 0.0;
+    let (mut video, mut audio) = ("v0".to_string(), "a0".to_string());
+
+    for i in 1..durations.len() {
+        // Each transition starts `duration` before the end of what's joined so far
+        offset += durations[i - 1] - duration;
+
+        let (video_out, audio_out) = if i == last {
+            ("v".to_string(), "a".to_string())
+        } else {
+            (format!("xv{}", i), format!("xa{}", i))
         };
-        
-        #[cfg(not(target_os = "windows"))]
-        let mut cmd = Command::new(&ffmpeg_path);
-        
-        cmd.args(&[
-            "-i", intro_path,
-            "-i", video_path,
-            "-filter_complex", "[0:v:0][0:a:0][1:v:0][1:a:0] concat=n=2:v=1:a=1 [v][a]",
-            "-map", "[v]",
-            "-map", "[a]",
-            "-c:v", codec,
-            "-preset", preset,
-            "-crf", &crf,
-            "-progress", progress_file,
-            "-y",
-            output_path.to_str().unwrap()
-        ])
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
-        
-        let status = cmd.status()?;
-        
-        if !status.success() {
-            return Err(anyhow!("FFmpeg failed to concatenate the videos"));
+
+        filter.push_str(&format!(
+            "[{}][v{}]xfade=transition={}:duration={}:offset={}[{}];",
+            video, i, name, duration, offset, video_out
+        ));
+        if with_audio {
+            filter.push_str(&format!("[{}][a{}]acrossfade=d={}[{}];", audio, i, duration, audio_out));
         }
+
+        (video, audio) = (video_out, audio_out);
     }
-    
-    Ok(output_path.to_str().unwrap().to_string())
+
+    // No trailing separator after the last chain
+    filter.pop();
+
+    durations.iter().sum::<f64>() - duration * last as f64
 }
 
+/// Sample rate of joined audio
+const JOIN_SAMPLE_RATE: u32 = 48000;
 
-pub fn add_intro(intro_path: &str, video_path: &str, output_dir: &str) -> Result<String> {
-    let ffmpeg_path = get_ffmpeg_path();  
-    
-    if !ffmpeg_path.exists() {
-        return Err(anyhow!("FFmpeg not found at {:?}", ffmpeg_path));
+/// Frame size, rate and audio every clip is converted to before joining
+struct JoinFormat {
+    width: u32,
+    height: u32,
+    framerate: f64,
+    /// Whether any clip has audio, clips without it get silence
+    with_audio: bool,
+}
+
+impl JoinFormat {
+    /// The largest clip's frame size and the highest frame rate, so the main
+    /// content isn't downscaled to match a smaller intro
+    fn for_clips(metadata: &[VideoMetadata]) -> Self {
+        let (width, height) = metadata
+            .iter()
+            .map(display_size)
+            .max_by_key(|&(width, height)| u64::from(width) * u64::from(height))
+            .unwrap_or((1920, 1080));
+
+        let framerate = metadata
+            .iter()
+            .map(|m| m.framerate)
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .fold(0.0, f64::max);
+
+        Self {
+            // yuv420p needs even dimensions
+            width: (width / 2 * 2).max(2),
+            height: (height / 2 * 2).max(2),
+            framerate: if framerate > 0.0 { framerate } else { 30.0 },
+            with_audio: metadata.iter().any(|m| !m.audio_streams.is_empty()),
+        }
     }
 
-    // Extract filenames (without extensions) for better naming
-    let intro_filename = Path::new(intro_path)
-        .file_stem()
-        .unwrap_or_default()
-        .to_string_lossy();
-    
-    let video_filename = Path::new(video_path)
-        .file_stem()
-        .unwrap_or_default()
-        .to_string_lossy();
+    /// Filters turning input `index` into `[v<index>]` and `[a<index>]`
+    fn normalize(&self, index: usize, clip: &VideoMetadata) -> String {
+        let mut filter = format!("[{}:v:0]", index);
 
-    // Generate a unique output filename with UUID
-    let unique_id = Uuid::new_v4();
-    let output_filename = format!("merged_{}_{}_{}.mp4", intro_filename, video_filename, unique_id);
-    let output_path = Path::new(output_dir).join(output_filename);
+        // Make the pixels square first so the clip keeps its shape
+        if clip.sample_aspect_ratio.as_deref().is_some_and(|sar| sar != "1:1" && sar != "0:1") {
+            filter.push_str("scale='trunc(iw*sar/2)*2':ih,setsar=1,");
+        }
+
+        // Fit inside the frame, letterboxing whatever is left
+        filter.push_str(&format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format=yuv420p,setpts=PTS-STARTPTS[v{i}];",
+            w = self.width,
+            h = self.height,
+            fps = self.framerate,
+            i = index
+        ));
+
+        if !self.with_audio {
+            return filter;
+        }
 
-    // Step 1: Try `-c:v copy` to avoid re-encoding
-    let status = Command::new(&ffmpeg_path)
-        .args(&[
-            "-i", intro_path,
-            "-i", video_path,
-            "-filter_complex", "[0:v:0][0:a:0][1:v:0][1:a:0] concat=n=2:v=1:a=1 [v][a]",
-            "-map", "[v]",
-            "-map", "[a]",
-            "-c:v", "copy", // Try to copy video without encoding
-            "-c:a", "aac",  // Encode audio for compatibility
-            "-b:a", "192k", 
-            "-strict", "experimental",
-            "-report", // Debugging: Logs errors to `ffmpeg-*.log`
-            "-y",  
-            output_path.to_str().unwrap()
-        ])
-        .status()?;
-
-    // Step 2: If `-c:v copy` fails, fallback to encoding with controlled bitrate
-    if !status.success() {
-        println!("⚠️ Warning: -c:v copy failed! Falling back to encoding...");
-
-        let status = Command::new(&ffmpeg_path)
-            .args(&[
-                "-i", intro_path,
-                "-i", video_path,
-                "-filter_complex", "[0:v:0][0:a:0][1:v:0][1:a:0] concat=n=2:v=1:a=1 [v][a]",
-                "-map", "[v]",
-                "-map", "[a]",
-                "-preset", "fast", // Faster encoding with smaller size
-                "-b:v", "2M",  // Set max bitrate (2 Mbps)
-                "-crf", "28",  // Reduce file size (higher = smaller)
-                "-y",  
-                output_path.to_str().unwrap()
-            ])
-            .status()?;
-
-        if !status.success() {
-            return Err(anyhow!("FFmpeg failed to concatenate the videos"));
+        if clip.audio_streams.is_empty() {
+            filter.push_str(&format!(
+                "anullsrc=r={}:cl=stereo,atrim=duration={}[a{}];",
+                JOIN_SAMPLE_RATE, clip.duration, index
+            ));
+        } else {
+            filter.push_str(&format!(
+                "[{}:a:0]aresample={},aformat=sample_fmts=fltp:channel_layouts=stereo[a{}];",
+                index, JOIN_SAMPLE_RATE, index
+            ));
         }
+
+        filter
     }
+}
 
-    Ok(output_path.to_str().unwrap().to_string())
+/// Width and height as played, after applying the rotation
+fn display_size(metadata: &VideoMetadata) -> (u32, u32) {
+    if metadata.rotation % 180 == 90 {
+        (metadata.height, metadata.width)
+    } else {
+        (metadata.width, metadata.height)
+    }
 }
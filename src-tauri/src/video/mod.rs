@@ -1,3 +1,11 @@
 pub mod cutter;
+pub mod detector;
 pub mod encoder;
-pub mod merger;
\ No newline at end of file
+pub mod frames;
+pub mod merger;
+pub mod preview;
+pub mod process;
+pub mod proxy;
+pub mod sprites;
+pub mod validator;
+pub mod waveform;
@@ -0,0 +1,66 @@
+use std::path::Path;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+use anyhow::Result;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::process::{Child, ChildStdout};
+use crate::error::Error;
+use crate::utils::{ffmpeg_command, get_ffmpeg_path};
+
+/// Default preview height, small enough to transcode faster than realtime
+pub const DEFAULT_PREVIEW_HEIGHT: u32 = 480;
+
+/// FFmpeg transcoding a file into fragmented MP4 the webview can play.
+///
+/// Reading from it yields the MP4 stream, dropping it kills FFmpeg, so a
+/// player that disconnects or seeks elsewhere doesn't leave work running.
+pub struct PreviewStream {
+    _child: Child,
+    stdout: ChildStdout,
+}
+
+impl AsyncRead for PreviewStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stdout).poll_read(cx, buf)
+    }
+}
+
+/// Start transcoding `input_path` from `start` seconds at `height` pixels
+pub fn start_preview(input_path: &Path, start: f64, height: u32) -> Result<PreviewStream> {
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args([
+        "-v", "error",
+        "-ss", &start.max(0.0).to_string(), // Input seeking, so the stream starts at `start`
+        "-i", input_path.to_str().unwrap(),
+        "-map", "0:v:0",
+        "-map", "0:a:0?",
+        // Never upscale, keep the width even for yuv420p
+        "-vf", &format!("scale=-2:'min({},ih)'", height),
+        "-c:v", "libx264",
+        "-preset", "veryfast",
+        "-tune", "zerolatency",
+        "-crf", "28",
+        "-pix_fmt", "yuv420p",
+        "-c:a", "aac",
+        "-b:a", "128k",
+        "-ac", "2",
+        // Fragmented MP4 can be played while it is being written
+        "-movflags", "frag_keyframe+empty_moov+default_base_moof",
+        "-f", "mp4",
+        "pipe:1",
+    ]);
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| Error::spawn(&get_ffmpeg_path(), e))?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    Ok(PreviewStream { _child: child, stdout })
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::path::Path;
+use std::process::{ExitStatus, Output, Stdio};
+use anyhow::Result;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
+
+/// Arguments making FFmpeg write machine-readable progress to stdout.
+/// These are global options and go before the first input.
+pub const PROGRESS_ARGS: [&str; 3] = ["-progress", "pipe:1", "-nostats"];
+
+/// Error returned when a job is cancelled while FFmpeg is running
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cancelled by user")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Check whether an error was caused by cancelling the job
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.is::<Cancelled>()
+}
+
+/// A progress update parsed from FFmpeg's `-progress` output
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Progress {
+    pub out_time: f64,        // seconds of output written so far
+    pub percent: f64,         // 0-100, relative to the expected duration
+    pub fps: Option<f64>,
+    pub speed: Option<f64>,   // multiple of realtime, e.g. 2.5 for "2.5x"
+    pub eta: Option<f64>,     // seconds until FFmpeg is done
+}
+
+/// Accumulates `key=value` lines until FFmpeg closes a progress block
+struct ProgressParser {
+    duration: f64,
+    current: Progress,
+}
+
+impl ProgressParser {
+    fn new(duration: f64) -> Self {
+        Self {
+            duration,
+            current: Progress::default(),
+        }
+    }
+
+    /// Feed one line, returns a snapshot at the end of each block
+    fn feed(&mut self, line: &str) -> Option<Progress> {
+        let (key, value) = line.trim().split_once('=')?;
+
+        match key {
+            // `out_time_ms` is in microseconds as well, kept for older builds
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<f64>() {
+                    self.current.out_time = (us / 1_000_000.0).max(0.0);
+                }
+            }
+            "fps" => self.current.fps = value.parse().ok(),
+            "speed" => self.current.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "progress" => {
+                let finished = value == "end";
+                if self.duration > 0.0 {
+                    self.current.percent = if finished {
+                        100.0
+                    } else {
+                        (self.current.out_time / self.duration * 100.0).clamp(0.0, 100.0)
+                    };
+                    self.current.eta = match self.current.speed {
+                        Some(speed) if speed > 0.0 => {
+                            Some(((self.duration - self.current.out_time) / speed).max(0.0))
+                        }
+                        _ => None,
+                    };
+                }
+                if finished {
+                    self.current.eta = Some(0.0);
+                }
+                return Some(self.current.clone());
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// Run an FFmpeg command until it exits or the job is cancelled.
+///
+/// On cancellation the child is killed and the partially written
+/// `output_path` is removed.
+pub async fn run(command: Command, output_path: &Path, cancel: &CancellationToken) -> Result<Output> {
+    let mut child = spawn(command, cancel)?;
+
+    // Drain both pipes in the background so FFmpeg never blocks on a full buffer
+    let stdout = tokio::spawn(read_all(child.stdout.take()));
+    let stderr = tokio::spawn(read_all(child.stderr.take()));
+
+    let status = wait(&mut child, output_path, cancel).await?;
+
+    Ok(Output {
+        status,
+        stdout: stdout.await.unwrap_or_default(),
+        stderr: stderr.await.unwrap_or_default(),
+    })
+}
+
+/// Like [`run`], for commands started with [`PROGRESS_ARGS`].
+///
+/// `duration` is the expected output length in seconds and is used to work
+/// out the percentage and ETA. Stdout is consumed by the progress reader.
+pub async fn run_with_progress(
+    command: Command,
+    output_path: &Path,
+    cancel: &CancellationToken,
+    duration: f64,
+    mut on_progress: impl FnMut(&Progress) + Send,
+) -> Result<Output> {
+    let mut child = spawn(command, cancel)?;
+    let stderr = tokio::spawn(read_all(child.stderr.take()));
+
+    let mut parser = ProgressParser::new(duration);
+    let mut lines = child.stdout.take().map(|stdout| BufReader::new(stdout).lines());
+
+    let status = loop {
+        let Some(reader) = lines.as_mut() else {
+            break wait(&mut child, output_path, cancel).await?;
+        };
+
+        tokio::select! {
+            line = reader.next_line() => match line {
+                Ok(Some(line)) => {
+                    if let Some(progress) = parser.feed(&line) {
+                        on_progress(&progress);
+                    }
+                }
+                // FFmpeg closed stdout, only the exit status is left
+                _ => lines = None,
+            },
+            _ = cancel.cancelled() => {
+                kill(&mut child, output_path).await;
+                return Err(Cancelled.into());
+            }
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr: stderr.await.unwrap_or_default(),
+    })
+}
+
+/// Like [`run`], handing stdout to `on_stdout` in chunks as FFmpeg writes it
+/// instead of buffering it, for commands that output raw data to `pipe:1`
+pub async fn run_streaming(
+    command: Command,
+    cancel: &CancellationToken,
+    mut on_stdout: impl FnMut(&[u8]) + Send,
+) -> Result<Output> {
+    let mut child = spawn(command, cancel)?;
+    let stderr = tokio::spawn(read_all(child.stderr.take()));
+    let mut stdout = child.stdout.take();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    let status = loop {
+        let Some(reader) = stdout.as_mut() else {
+            break wait(&mut child, Path::new(""), cancel).await?;
+        };
+
+        tokio::select! {
+            read = reader.read(&mut buffer) => match read {
+                Ok(0) | Err(_) => stdout = None,
+                Ok(n) => on_stdout(&buffer[..n]),
+            },
+            _ = cancel.cancelled() => {
+                let _ = child.kill().await;
+                return Err(Cancelled.into());
+            }
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr: stderr.await.unwrap_or_default(),
+    })
+}
+
+fn spawn(mut command: Command, cancel: &CancellationToken) -> Result<Child> {
+    if cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+
+    Ok(command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?)
+}
+
+async fn wait(child: &mut Child, output_path: &Path, cancel: &CancellationToken) -> Result<ExitStatus> {
+    tokio::select! {
+        status = child.wait() => Ok(status?),
+        _ = cancel.cancelled() => {
+            kill(child, output_path).await;
+            Err(Cancelled.into())
+        }
+    }
+}
+
+async fn kill(child: &mut Child, output_path: &Path) {
+    let _ = child.kill().await;
+    let _ = tokio::fs::remove_file(output_path).await;
+}
+
+async fn read_all<R: AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buffer).await;
+    }
+    buffer
+}
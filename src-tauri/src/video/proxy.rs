@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use super::{cutter, process::{self, Progress, PROGRESS_ARGS}};
+use crate::commands::media::ProxySettings;
+use crate::error::Error;
+use crate::utils::{cache_dir, file_fingerprint, ffmpeg_command};
+
+/// Where the proxy for `source_path` lives. The name is derived from the
+/// source's path, size and modification time, so an unchanged source maps to
+/// the same proxy across sessions and an edited one gets a new proxy.
+pub fn proxy_path(source_path: &str) -> Result<PathBuf> {
+    let fingerprint = file_fingerprint(source_path)?;
+    Ok(cache_dir("proxies")?.join(format!("{}.mp4", fingerprint)))
+}
+
+/// The finished proxy for `source_path`, if one has been generated
+pub fn existing_proxy(source_path: &str) -> Option<PathBuf> {
+    proxy_path(source_path).ok().filter(|path| path.is_file())
+}
+
+/// Generate the proxy for `source_path`, or return the existing one
+pub async fn generate_proxy(
+    source_path: &str,
+    settings: &ProxySettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<String> {
+    let output_path = proxy_path(source_path)?;
+
+    if output_path.is_file() {
+        return Ok(output_path.to_str().unwrap().to_string());
+    }
+
+    // Write next to the final file and rename once complete, so a crash or
+    // cancel never leaves a truncated proxy that would be reused
+    let partial_path = output_path.with_extension("part.mp4");
+
+    // Duration is only used for the percentage, so a failed probe isn't fatal
+    let duration = cutter::get_metadata(source_path).await.map(|m| m.duration).unwrap_or_default();
+    let keyframe_interval = settings.keyframe_interval.max(1).to_string();
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args(PROGRESS_ARGS).args([
+        "-i", source_path,
+        "-map", "0:v:0",
+        "-map", "0:a:0?",
+        "-vf", &format!("scale=-2:'min({},ih)'", settings.height),
+        "-c:v", "libx264",
+        "-preset", "veryfast",
+        "-crf", &settings.quality.to_string(),
+        // Short, fixed GOPs keep seeking cheap; an interval of 1 is all-intra
+        "-g", &keyframe_interval,
+        "-keyint_min", &keyframe_interval,
+        "-sc_threshold", "0",
+        "-pix_fmt", "yuv420p",
+        "-c:a", "aac",
+        "-b:a", "128k",
+        "-movflags", "+faststart",
+        "-y",
+        partial_path.to_str().unwrap(),
+    ]);
+
+    let output = process::run_with_progress(cmd, &partial_path, cancel, duration, on_progress).await?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&partial_path);
+        return Err(Error::ffmpeg("FFmpeg failed to generate the proxy", &output).into());
+    }
+
+    std::fs::rename(&partial_path, &output_path)?;
+
+    Ok(output_path.to_str().unwrap().to_string())
+}
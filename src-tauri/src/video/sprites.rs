@@ -0,0 +1,169 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use super::{cutter, process::{self, Progress, PROGRESS_ARGS}};
+use crate::commands::media::SpriteSettings;
+use crate::error::Error;
+use crate::utils::{cache_dir, file_fingerprint, ffmpeg_command};
+
+/// Name of the WebVTT index inside a sprite directory
+pub const THUMBNAILS_VTT: &str = "thumbnails.vtt";
+
+/// Directory holding the sprite sheets and WebVTT index for `source_path`.
+/// Like proxies it is keyed by the source's path, size and modification time.
+pub fn sprite_dir(source_path: &str) -> Result<PathBuf> {
+    Ok(cache_dir("sprites")?.join(file_fingerprint(source_path)?))
+}
+
+/// Whether sprites for the current version of `source_path` are complete
+pub fn has_sprites(source_path: &str) -> bool {
+    sprite_dir(source_path).is_ok_and(|dir| dir.join(THUMBNAILS_VTT).is_file())
+}
+
+/// Sample frames, tile them into sprite sheets and write a WebVTT file
+/// mapping time ranges to `#xywh` rectangles. Returns the VTT path.
+pub async fn generate_sprites(
+    source_path: &str,
+    settings: &SpriteSettings,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(&Progress) + Send,
+) -> Result<String> {
+    let output_dir = sprite_dir(source_path)?;
+    let vtt_path = output_dir.join(THUMBNAILS_VTT);
+
+    if vtt_path.is_file() {
+        return Ok(vtt_path.to_str().unwrap().to_string());
+    }
+
+    let metadata = cutter::get_metadata(source_path).await?;
+
+    // Tiles need a fixed size to address them in the VTT, so work it out
+    // from the displayed aspect ratio instead of letting FFmpeg pick
+    let (width, height) = if metadata.rotation % 180 == 90 {
+        (metadata.height, metadata.width)
+    } else {
+        (metadata.width, metadata.height)
+    };
+    let tile_width = settings.width.max(16);
+    let tile_height = ((tile_width as f64 * height as f64 / width.max(1) as f64 / 2.0).round() as u32 * 2).max(2);
+    let columns = settings.columns.max(1);
+    let rows = settings.rows.max(1);
+
+    let sampler = match settings.scene_threshold {
+        // Always keep the first frame so the start of the video has a thumbnail
+        Some(threshold) => format!("select='eq(n,0)+gt(scene,{})'", threshold),
+        None => format!("fps=1/{}", settings.interval.max(0.1)),
+    };
+
+    // Build in a scratch directory and move it into place once the VTT is
+    // written, so a cancelled run never looks complete
+    let work_dir = output_dir.with_extension(Uuid::new_v4().simple().to_string());
+    std::fs::create_dir_all(&work_dir)?;
+
+    let result = async {
+        let mut cmd = ffmpeg_command()?;
+
+        cmd.args(PROGRESS_ARGS).args([
+            "-hide_banner",
+            "-i", source_path,
+            "-an",
+            "-sn",
+            // showinfo logs the timestamp of every sampled frame for the VTT
+            "-vf", &format!("{},showinfo,scale={}:{},tile={}x{}", sampler, tile_width, tile_height, columns, rows),
+            "-fps_mode", "passthrough",
+            "-q:v", "4",
+            "-y",
+            work_dir.join("sprite_%03d.jpg").to_str().unwrap(),
+        ]);
+
+        let output = process::run_with_progress(cmd, &work_dir, cancel, metadata.duration, on_progress).await?;
+
+        if !output.status.success() {
+            return Err(Error::ffmpeg("FFmpeg failed to generate thumbnails", &output).into());
+        }
+
+        let times = sample_times(&String::from_utf8_lossy(&output.stderr));
+        let vtt = thumbnails_vtt(&times, metadata.duration, tile_width, tile_height, columns, rows);
+        std::fs::write(work_dir.join(THUMBNAILS_VTT), vtt)?;
+
+        // Another run may have finished first, in which case its output wins
+        if !vtt_path.is_file() {
+            let _ = std::fs::remove_dir_all(&output_dir);
+            std::fs::rename(&work_dir, &output_dir)?;
+        }
+
+        Ok(vtt_path.to_str().unwrap().to_string())
+    }
+    .await;
+
+    let _ = std::fs::remove_dir_all(&work_dir);
+
+    result
+}
+
+/// `pts_time` of each frame reported by the showinfo filter
+fn sample_times(stderr: &str) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| {
+            let rest = line.split("pts_time:").nth(1)?;
+            rest.split_whitespace().next()?.parse::<f64>().ok()
+        })
+        .collect()
+}
+
+fn thumbnails_vtt(times: &[f64], duration: f64, tile_width: u32, tile_height: u32, columns: u32, rows: u32) -> String {
+    let per_sheet = (columns * rows) as usize;
+    let mut vtt = String::from("WEBVTT\n");
+
+    for (i, &start) in times.iter().enumerate() {
+        let end = times.get(i + 1).copied().unwrap_or(duration).max(start);
+        let sheet = i / per_sheet + 1;
+        let tile = (i % per_sheet) as u32;
+        let x = (tile % columns) * tile_width;
+        let y = (tile / columns) * tile_height;
+
+        let _ = write!(
+            vtt,
+            "\n{} --> {}\nsprite_{:03}.jpg#xywh={},{},{},{}\n",
+            vtt_timestamp(start),
+            vtt_timestamp(end),
+            sheet,
+            x,
+            y,
+            tile_width,
+            tile_height,
+        );
+    }
+
+    vtt
+}
+
+/// Format seconds as `HH:MM:SS.mmm`
+fn vtt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// A file inside the sprite directory of `source_path`, for the server.
+/// Only plain file names are accepted so requests can't leave the directory.
+pub fn sprite_file(source_path: &Path, name: &str) -> Option<PathBuf> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'))
+        && !name.starts_with('.');
+
+    if !valid {
+        return None;
+    }
+
+    Some(sprite_dir(source_path.to_str()?).ok()?.join(name))
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use super::super::commands::video::VideoSegment;
+
+/// Problem found in a segment. Errors stop the job, warnings are fixed up
+/// (e.g. by clamping) and reported.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SegmentIssue {
+    /// Start before 0, clamped to 0
+    NegativeStart,
+    /// End after the end of the video, clamped to `duration`
+    EndBeyondDuration { duration: f64 },
+    /// Start at or after the end of the video
+    StartBeyondDuration { duration: f64 },
+    /// End isn't after start, or a time isn't a number
+    EmptyRange,
+    /// Shares part of its range with another segment
+    Overlap { other: usize },
+    /// `output_name` can't be used as a file name
+    InvalidOutputName { reason: String },
+}
+
+/// Validation result for one segment
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SegmentDiagnostic {
+    pub index: usize,
+    pub start_time: f64, // clamped to the video
+    pub end_time: f64,   // clamped to the video
+    pub errors: Vec<SegmentIssue>,
+    pub warnings: Vec<SegmentIssue>,
+}
+
+impl SegmentDiagnostic {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Check segments against a video of `duration` seconds
+pub fn validate(segments: &[VideoSegment], duration: f64) -> Vec<SegmentDiagnostic> {
+    let mut diagnostics: Vec<SegmentDiagnostic> = segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| check_segment(index, segment, duration))
+        .collect();
+
+    // Overlaps are only meaningful between ranges that are valid on their own
+    for i in 0..diagnostics.len() {
+        for j in (i + 1)..diagnostics.len() {
+            let (a, b) = (&diagnostics[i], &diagnostics[j]);
+            if !a.is_valid() || !b.is_valid() {
+                continue;
+            }
+
+            if a.start_time < b.end_time && b.start_time < a.end_time {
+                diagnostics[i].warnings.push(SegmentIssue::Overlap { other: j });
+                diagnostics[j].warnings.push(SegmentIssue::Overlap { other: i });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn check_segment(index: usize, segment: &VideoSegment, duration: f64) -> SegmentDiagnostic {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut start_time = segment.start_time();
+    let mut end_time = segment.end_time();
+
+    if !start_time.is_finite() || !end_time.is_finite() {
+        errors.push(SegmentIssue::EmptyRange);
+    } else {
+        if start_time < 0.0 {
+            warnings.push(SegmentIssue::NegativeStart);
+            start_time = 0.0;
+        }
+
+        if start_time >= duration {
+            errors.push(SegmentIssue::StartBeyondDuration { duration });
+        } else if end_time > duration {
+            warnings.push(SegmentIssue::EndBeyondDuration { duration });
+            end_time = duration;
+        }
+
+        if end_time <= start_time {
+            errors.push(SegmentIssue::EmptyRange);
+        }
+    }
+
+    if let Some(reason) = invalid_name_reason(segment.output_name()) {
+        errors.push(SegmentIssue::InvalidOutputName { reason });
+    }
+
+    SegmentDiagnostic {
+        index,
+        start_time,
+        end_time,
+        errors,
+        warnings,
+    }
+}
+
+/// Why `name` can't be used as an output file name on any platform
+pub fn invalid_name_reason(name: &str) -> Option<String> {
+    if name.trim().is_empty() {
+        return Some("Name is empty".to_string());
+    }
+
+    if name.contains(['/', '\\']) {
+        return Some("Name contains a path separator".to_string());
+    }
+
+    if name == "." || name == ".." {
+        return Some("Name refers to a directory".to_string());
+    }
+
+    if let Some(c) = name.chars().find(|c| matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') || c.is_control()) {
+        return Some(format!("Name contains the reserved character {:?}", c));
+    }
+
+    // Windows refuses names ending in a dot or space
+    if name.ends_with(['.', ' ']) {
+        return Some("Name ends with a dot or space".to_string());
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64, name: &str) -> VideoSegment {
+        VideoSegment::new(start, end, name.to_string())
+    }
+
+    #[test]
+    fn clamps_negative_start_and_late_end() {
+        let diagnostic = &validate(&[segment(-2.0, 15.0, "a")], 10.0)[0];
+
+        assert!(diagnostic.is_valid());
+        assert_eq!((diagnostic.start_time, diagnostic.end_time), (0.0, 10.0));
+        assert!(matches!(diagnostic.warnings[..], [SegmentIssue::NegativeStart, SegmentIssue::EndBeyondDuration { .. }]));
+    }
+
+    #[test]
+    fn rejects_ranges_outside_or_empty() {
+        let diagnostics = validate(
+            &[segment(12.0, 14.0, "a"), segment(5.0, 5.0, "b"), segment(f64::NAN, 3.0, "c")],
+            10.0,
+        );
+
+        assert!(matches!(diagnostics[0].errors[..], [SegmentIssue::StartBeyondDuration { .. }]));
+        assert!(matches!(diagnostics[1].errors[..], [SegmentIssue::EmptyRange]));
+        assert!(matches!(diagnostics[2].errors[..], [SegmentIssue::EmptyRange]));
+    }
+
+    #[test]
+    fn end_clamped_onto_start_is_empty() {
+        let diagnostic = &validate(&[segment(-3.0, -1.0, "a")], 10.0)[0];
+
+        assert!(matches!(diagnostic.errors[..], [SegmentIssue::EmptyRange]));
+    }
+
+    #[test]
+    fn warns_about_overlaps_between_valid_segments() {
+        let diagnostics = validate(&[segment(0.0, 5.0, "a"), segment(4.0, 8.0, "b"), segment(8.0, 9.0, "c")], 10.0);
+
+        assert!(matches!(diagnostics[0].warnings[..], [SegmentIssue::Overlap { other: 1 }]));
+        assert!(matches!(diagnostics[1].warnings[..], [SegmentIssue::Overlap { other: 0 }]));
+        // Touching ranges don't overlap
+        assert!(diagnostics[2].warnings.is_empty());
+    }
+
+    #[test]
+    fn ignores_overlaps_with_invalid_segments() {
+        let diagnostics = validate(&[segment(0.0, 5.0, "a"), segment(4.0, 8.0, "b/c")], 10.0);
+
+        assert!(diagnostics[0].warnings.is_empty());
+        assert!(matches!(diagnostics[1].errors[..], [SegmentIssue::InvalidOutputName { .. }]));
+    }
+
+    #[test]
+    fn accepts_duplicate_names() {
+        // Outputs are numbered when their names collide
+        let diagnostics = validate(&[segment(0.0, 1.0, "Segment_2"), segment(2.0, 3.0, "Segment_2")], 10.0);
+
+        assert!(diagnostics.iter().all(SegmentDiagnostic::is_valid));
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in ["", "  ", "a/b", "a\\b", ".", "..", "a:b", "a?", "name.", "name "] {
+            assert!(invalid_name_reason(name).is_some(), "{:?} should be invalid", name);
+        }
+        assert!(invalid_name_reason("clip 01.final").is_none());
+    }
+}
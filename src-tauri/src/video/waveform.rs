@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use super::process;
+use crate::commands::media::Waveform;
+use crate::error::Error;
+use crate::utils::{cache_dir, file_fingerprint, ffmpeg_command};
+
+/// Rate audio is decoded at. Plenty for drawing, and keeps decoding cheap.
+const SAMPLE_RATE: u32 = 8000;
+
+/// Resolution of the cached peaks, every request is derived from these
+const PEAKS_PER_SECOND: u32 = 100;
+
+const SAMPLES_PER_PEAK: usize = (SAMPLE_RATE / PEAKS_PER_SECOND) as usize;
+
+/// Min/max peaks of an audio stream, `buckets` pairs between `start` and
+/// `end` seconds. `stream_index` is the absolute stream index as reported in
+/// `VideoMetadata::audio_streams`, or the first audio stream when `None`.
+pub async fn get_waveform(
+    video_path: &str,
+    stream_index: Option<u32>,
+    buckets: usize,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> Result<Waveform> {
+    let peaks = peaks(video_path, stream_index).await?;
+    let duration = peaks.len() as f64 / PEAKS_PER_SECOND as f64;
+
+    let start = start.unwrap_or(0.0).clamp(0.0, duration);
+    let end = end.unwrap_or(duration).clamp(start, duration);
+    let first = (start * PEAKS_PER_SECOND as f64) as usize;
+    let last = ((end * PEAKS_PER_SECOND as f64).ceil() as usize).min(peaks.len());
+    let window = &peaks[first..last.max(first)];
+
+    // Never return more buckets than there are cached peaks in the window
+    let buckets = buckets.clamp(1, window.len().max(1));
+
+    let peaks = (0..buckets)
+        .filter_map(|bucket| {
+            let from = bucket * window.len() / buckets;
+            let to = ((bucket + 1) * window.len() / buckets).max(from + 1).min(window.len());
+            let slice = window.get(from..to)?;
+
+            let min = slice.iter().map(|p| p[0]).min()?;
+            let max = slice.iter().map(|p| p[1]).max()?;
+            Some([min as f32 / i16::MAX as f32, max as f32 / i16::MAX as f32])
+        })
+        .collect();
+
+    Ok(Waveform {
+        start,
+        end,
+        duration,
+        peaks,
+    })
+}
+
+/// Full-resolution peaks, decoded once per file and stream and cached on disk
+async fn peaks(video_path: &str, stream_index: Option<u32>) -> Result<Vec<[i16; 2]>> {
+    let cache_path = cache_path(video_path, stream_index)?;
+
+    if let Ok(bytes) = std::fs::read(&cache_path) {
+        return Ok(bytes
+            .chunks_exact(4)
+            .map(|c| [i16::from_le_bytes([c[0], c[1]]), i16::from_le_bytes([c[2], c[3]])])
+            .collect());
+    }
+
+    let stream = match stream_index {
+        Some(index) => format!("0:{}", index),
+        None => "0:a:0".to_string(),
+    };
+
+    let mut cmd = ffmpeg_command()?;
+
+    cmd.args([
+        "-v", "error",
+        "-i", video_path,
+        "-map", &stream,
+        "-ac", "1",
+        "-ar", &SAMPLE_RATE.to_string(),
+        "-c:a", "pcm_s16le",
+        "-f", "s16le",
+        "pipe:1",
+    ]);
+
+    // Reduce to peaks while decoding, the raw samples of a long recording
+    // would take hundreds of megabytes
+    let mut peaks = Vec::new();
+    let mut current = [i16::MAX, i16::MIN];
+    let mut count = 0;
+    let mut carry: Option<u8> = None;
+
+    let mut push_sample = |sample: i16| {
+        current = [current[0].min(sample), current[1].max(sample)];
+        count += 1;
+        if count == SAMPLES_PER_PEAK {
+            peaks.push(current);
+            current = [i16::MAX, i16::MIN];
+            count = 0;
+        }
+    };
+
+    let output = process::run_streaming(cmd, &CancellationToken::new(), |chunk| {
+        let mut bytes = chunk;
+
+        // A sample may be split across two reads
+        if let Some(low) = carry.take() {
+            if let Some((&high, rest)) = bytes.split_first() {
+                push_sample(i16::from_le_bytes([low, high]));
+                bytes = rest;
+            }
+        }
+
+        let mut samples = bytes.chunks_exact(2);
+        for sample in &mut samples {
+            push_sample(i16::from_le_bytes([sample[0], sample[1]]));
+        }
+        carry = samples.remainder().first().copied();
+    })
+    .await?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("FFmpeg failed to decode audio", &output).into());
+    }
+
+    if count > 0 {
+        peaks.push(current);
+    }
+
+    let bytes: Vec<u8> = peaks
+        .iter()
+        .flat_map(|[min, max]| min.to_le_bytes().into_iter().chain(max.to_le_bytes()))
+        .collect();
+
+    // Write then rename so a concurrent request never reads half a file
+    let partial_path = cache_path.with_extension(Uuid::new_v4().simple().to_string());
+    if std::fs::write(&partial_path, bytes).is_ok() && std::fs::rename(&partial_path, &cache_path).is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+
+    Ok(peaks)
+}
+
+fn cache_path(video_path: &str, stream_index: Option<u32>) -> Result<PathBuf> {
+    let stream = stream_index.map_or("a".to_string(), |index| index.to_string());
+    Ok(cache_dir("waveforms")?.join(format!("{}_{}.peaks", file_fingerprint(video_path)?, stream)))
+}
@@ -51,7 +51,7 @@ const IconButton = ({ onClick, children, className = "" }: {
 );
 
 const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
-    const { videoPath } = useVideoStore();
+    const { videoPath, metadata } = useVideoStore();
     const videoRef = useRef<HTMLVideoElement | null>(null);
 
     const [isPlaying, setIsPlaying] = useState(false);
@@ -64,6 +64,10 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
     const [isHovered, setIsHovered] = useState(false);
     const [playbackSpeed, setPlaybackSpeed] = useState(1);
     const [showDebug, setShowDebug] = useState(false);
+    // Set when the webview can't decode the file and we play a transcoded preview,
+    // which starts at `previewOffset` seconds into the source
+    const [previewUrl, setPreviewUrl] = useState<string | null>(null);
+    const [previewOffset, setPreviewOffset] = useState(0);
 
     async function getVideoStreamUrl(videoPath: string): Promise<string | null> {
         if (!videoPath) {
@@ -78,8 +82,10 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
                 return null;
             }
 
-            const encodedPath = encodeURIComponent(videoPath);
-            const fullUrl = `${serverUrl}/video/${encodedPath}`;
+            // The server only serves files opened in this session, by ID and token.
+            // Play the proxy when one has been generated, edits still use the original.
+            const proxyUrl = await invoke<string | null>("get_proxy_url", { path: videoPath });
+            const fullUrl = proxyUrl ?? await invoke<string>("get_video_url", { path: videoPath });
             setVideoUrl(fullUrl);
             return fullUrl;
         } catch (err) {
@@ -94,6 +100,9 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
             return;
         }
 
+        setPreviewUrl(null);
+        setPreviewOffset(0);
+
         getVideoStreamUrl(videoPath).then((url) => {
             if (url && videoRef.current) {
                 videoRef.current.src = url;
@@ -104,16 +113,49 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
         });
     }, [videoPath]);
 
+    // Load the transcoded preview starting at `time` seconds into the source
+    const loadPreview = (url: string, time: number) => {
+        if (!videoRef.current) return;
+
+        const wasPlaying = !videoRef.current.paused;
+        setPreviewOffset(time);
+        videoRef.current.src = `${url}?t=${time}`;
+        videoRef.current.load();
+        if (wasPlaying) {
+            videoRef.current.play().catch(() => setIsPlaying(false));
+        }
+    };
+
+    // Fall back to the preview stream for codecs the webview can't play
+    const handleVideoError = async () => {
+        const mediaError = videoRef.current?.error;
+        if (!videoPath || previewUrl || mediaError?.code !== MediaError.MEDIA_ERR_SRC_NOT_SUPPORTED) {
+            setError(`Playback Error: ${mediaError?.message || "Unknown error"}`);
+            return;
+        }
+
+        try {
+            const url = await invoke<string>("get_preview_url", { path: videoPath });
+            setPreviewUrl(url);
+            setVideoUrl(url);
+            loadPreview(url, 0);
+        } catch (err) {
+            setError(`Error: Failed to start preview. ${err}`);
+        }
+    };
+
     const handleVideoLoaded = () => {
         if (videoRef.current) {
-            setDuration(videoRef.current.duration);
+            // The preview is a live stream with no duration of its own
+            setDuration(previewUrl ? metadata?.duration ?? 0 : videoRef.current.duration);
         }
     };
 
     const handleTimeUpdate = () => {
         if (videoRef.current) {
-            setCurrentTime(videoRef.current.currentTime);
-            if (onTimeUpdate) onTimeUpdate(videoRef.current.currentTime);
+            const time = previewOffset + videoRef.current.currentTime;
+            setCurrentTime(time);
+            if (onTimeUpdate) onTimeUpdate(time);
         }
     };
 
@@ -131,7 +173,11 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
     };
 
     const handleProgressChange = (value: number) => {
-        if (videoRef.current) {
+        if (previewUrl) {
+            // The preview can't seek, so restart the transcode at the new time
+            loadPreview(previewUrl, value);
+            setCurrentTime(value);
+        } else if (videoRef.current) {
             videoRef.current.currentTime = value;
             setCurrentTime(value);
         }
@@ -174,6 +220,7 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
                 playsInline
                 onLoadedData={handleVideoLoaded}
                 onTimeUpdate={handleTimeUpdate}
+                onError={handleVideoError}
                 onClick={togglePlay}
             />
 
@@ -193,6 +240,7 @@ const VideoPlayer: React.FC<VideoPlayerProps> = ({ onTimeUpdate }) => {
                 <div className="absolute top-10 right-2 bg-gray-800 text-white p-2 rounded-lg text-xs">
                     <p><strong>File:</strong> {videoPath}</p>
                     <p><strong>Streaming:</strong> {videoUrl ? videoUrl : "No"}</p>
+                    {previewUrl && <p><strong>Preview:</strong> transcoded from {previewOffset.toFixed(1)}s</p>}
                     {error && <p><strong>Error:</strong> {error}</p>}
                 </div>
             )}
@@ -13,7 +13,7 @@ import VideoPlayer from "../components/video/VideoPlayer"
 import Timeline from "../components/video/Timeline"
 import SegmentEditor from "../components/video/SegmentEditor"
 import CompressionSettings from "../components/video/CompressionSettings"
-import { loadVideo, selectFile, selectDirectory, processVideo, checkDependencies, cutVideo } from "../services/tauriApi"
+import { loadVideo, selectFile, selectDirectory, processVideo, checkDependencies, cutVideo, describeError } from "../services/tauriApi"
 import { Button } from "@/components/ui/button"
 import { Input } from "@/components/ui/input"
 import { ScrollArea } from "@/components/ui/scroll-area"
@@ -69,7 +69,7 @@ const VideoEditPage: React.FC = () => {
             setMetadata(videoMetadata)
             setCurrentStep("edit")
         } catch (error) {
-            setError(`${error}`)
+            setError(describeError(error))
         }
     }
 
@@ -142,7 +142,7 @@ const VideoEditPage: React.FC = () => {
             setProcessingResults(results);
             setCurrentStep("process");
         } catch (error) {
-            setError(`Error processing videos: ${error}`);
+            setError(`Error processing videos: ${describeError(error)}`);
         } finally {
             setProcessingState(false);
             clearInterval(newTimer);
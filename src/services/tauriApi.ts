@@ -1,8 +1,139 @@
 import { invoke } from '@tauri-apps/api/core';
-import { VideoSegment, VideoMetadata, CompressionSettings, ProcessingResult, SegmentProgress } from '../types';
-import { getCurrentWindow } from '@tauri-apps/api/window';
+import { VideoSegment, VideoMetadata, CompressionSettings, ProcessingResult, SegmentProgress, JobFinished, JobRecord, CommandError, SegmentDiagnostic, ProxySettings, ImageFormat, SpriteSettings, Waveform, SceneDetection, SceneDetected, TimeRange, SilenceSettings, TimelineClip, Transition } from '../types';
 import { listen } from '@tauri-apps/api/event';
 
+// Human-readable message for an error thrown by a command
+export function describeError(error: unknown): string {
+  if (typeof error !== 'object' || error === null || !('kind' in error)) {
+    return `${error}`;
+  }
+
+  const e = error as CommandError;
+  switch (e.kind) {
+    case 'ffmpeg_not_found':
+      return `FFmpeg not found at ${e.path}`;
+    case 'probe_failed':
+      return `Could not read ${e.path}`;
+    case 'ffmpeg_failed':
+      return e.exit_code !== undefined && e.exit_code !== null ? `${e.context} (exit code ${e.exit_code})` : e.context;
+    case 'invalid_segment':
+      return `Invalid segment: ${e.message}`;
+    case 'encoder_unavailable':
+      return `Encoder ${e.encoder} is not available in this FFmpeg build`;
+    case 'disk_full':
+      return "Not enough disk space to write the output";
+    case 'cancelled':
+      return "Cancelled by user";
+    case 'job_not_found':
+      return `No job with ID ${e.job_id}`;
+    default:
+      return e.message;
+  }
+}
+
+// Long-running commands return a job ID and report their results through a
+// `job_finished` event. This starts the job and resolves once it is done.
+// `segment_progress` events of this job only, not of other jobs such as the
+// background thumbnails, are passed to `onProgress`.
+async function runJob(
+  command: string,
+  args: Record<string, unknown>,
+  onProgress?: (progress: SegmentProgress) => void
+): Promise<ProcessingResult[]> {
+  let jobId: string | null = null;
+  const finished = new Map<string, ProcessingResult[]>();
+  const earlyProgress: SegmentProgress[] = [];
+  let resolveJob: (results: ProcessingResult[]) => void = () => {};
+  const done = new Promise<ProcessingResult[]>((resolve) => { resolveJob = resolve; });
+
+  // Listen before invoking so a job that finishes immediately isn't missed
+  const unlisten = await listen<JobFinished>("job_finished", (event) => {
+    const { job_id, results } = event.payload;
+    if (job_id === jobId) {
+      resolveJob(results);
+    } else {
+      finished.set(job_id, results);
+    }
+  });
+
+  const unlistenProgress = onProgress
+    ? await listen<SegmentProgress>("segment_progress", (event) => {
+        if (jobId === null) {
+          earlyProgress.push(event.payload);
+        } else if (event.payload.job_id === jobId) {
+          onProgress(event.payload);
+        }
+      })
+    : () => {};
+
+  try {
+    jobId = await invoke<string>(command, args);
+    earlyProgress.filter((progress) => progress.job_id === jobId).forEach((progress) => onProgress?.(progress));
+    const early = finished.get(jobId);
+    if (early) {
+      resolveJob(early);
+    }
+    return await done;
+  } finally {
+    unlisten();
+    unlistenProgress();
+  }
+}
+
+// Output path of a single-result job, throws if the job failed
+async function runSingleJob(command: string, args: Record<string, unknown>): Promise<string> {
+  const [result] = await runJob(command, args);
+  if (!result?.success || !result.output_path) {
+    throw result?.error ?? result?.error_message ?? "Job produced no output";
+  }
+  return result.output_path;
+}
+
+export async function cancelJob(jobId: string): Promise<void> {
+  try {
+    await invoke<void>('cancel_job', { jobId });
+  } catch (error) {
+    console.error("Error cancelling job:", error);
+    throw error;
+  }
+}
+
+export async function listJobs(): Promise<JobRecord[]> {
+  try {
+    return await invoke<JobRecord[]>('list_jobs');
+  } catch (error) {
+    console.error("Error listing jobs:", error);
+    throw error;
+  }
+}
+
+export async function getJob(jobId: string): Promise<JobRecord> {
+  try {
+    return await invoke<JobRecord>('get_job', { jobId });
+  } catch (error) {
+    console.error("Error getting job:", error);
+    throw error;
+  }
+}
+
+export async function resumeJob(jobId: string): Promise<void> {
+  try {
+    await invoke<void>('resume_job', { jobId });
+  } catch (error) {
+    console.error("Error resuming job:", error);
+    throw error;
+  }
+}
+
+export async function setJobConcurrency(limit: number): Promise<void> {
+  try {
+    await invoke<void>('set_job_concurrency', { limit });
+  } catch (error) {
+    console.error("Error setting job concurrency:", error);
+    throw error;
+  }
+}
+
 export async function loadVideo(path: string): Promise<VideoMetadata> {
   try {
     return await invoke<VideoMetadata>('load_video', { path });
@@ -21,6 +152,27 @@ export async function getVideoMetadata(path: string): Promise<VideoMetadata> {
   }
 }
 
+export async function getKeyframes(path: string): Promise<number[]> {
+  try {
+    return await invoke<number[]>('get_keyframes', { path });
+  } catch (error) {
+    console.error("Error getting keyframes:", error);
+    throw error;
+  }
+}
+
+export async function validateSegments(
+  inputPath: string,
+  segments: VideoSegment[]
+): Promise<SegmentDiagnostic[]> {
+  try {
+    return await invoke<SegmentDiagnostic[]>('validate_segments', { inputPath, segments });
+  } catch (error) {
+    console.error("Error validating segments:", error);
+    throw error;
+  }
+}
+
 export async function cutVideo(
   inputPath: string,
   segments: VideoSegment[],
@@ -28,20 +180,11 @@ export async function cutVideo(
   onProgress: (progress: SegmentProgress) => void
 ): Promise<ProcessingResult[]> {
   try {
-    // Listen for progress updates from Rust
-    const unlisten = await listen("segment_progress", (event) => {
-      const progressData = event.payload as SegmentProgress
-      onProgress(progressData) // Call the progress callback
-    })
-
-    const result = await invoke<ProcessingResult[]>("cut_video_with_progress", {
+    return await runJob("cut_video_with_progress", {
       inputPath,
       segments,
       outputDir
-    })
-
-    unlisten() // Stop listening when processing is complete
-    return result
+    }, onProgress)
   } catch (error) {
     console.error("Error cutting video:", error)
     throw error
@@ -51,13 +194,15 @@ export async function cutVideo(
 export async function addIntro(
   introPath: string,
   videoPath: string,
-  outputDir: string
+  outputDir: string,
+  transition?: Transition
 ): Promise<string> {
   try {
-    return await invoke<string>('add_intro', {
+    return await runSingleJob('add_intro', {
       introPath,
       videoPath,
-      outputDir
+      outputDir,
+      transition
     });
   } catch (error) {
     console.error("Error adding intro:", error);
@@ -71,7 +216,7 @@ export async function compressVideo(
   settings: CompressionSettings
 ): Promise<string> {
   try {
-    return await invoke<string>('compress_video', {
+    return await runSingleJob('compress_video', {
       inputPath,
       outputDir,
       settings
@@ -82,6 +227,147 @@ export async function compressVideo(
   }
 }
 
+// Cuts every clip and joins them in order into one file, returns its path
+export async function renderTimeline(
+  clips: TimelineClip[],
+  outputDir: string,
+  outputName: string,
+  compressionSettings?: CompressionSettings,
+  transition?: Transition
+): Promise<string> {
+  try {
+    return await runSingleJob('render_timeline', {
+      clips,
+      outputDir,
+      outputName,
+      compressionSettings,
+      transition
+    });
+  } catch (error) {
+    console.error("Error rendering timeline:", error);
+    throw error;
+  }
+}
+
+// Generates (or reuses) a low-resolution proxy that the player uses instead of the source
+export async function generateProxy(
+  path: string,
+  settings?: ProxySettings
+): Promise<string> {
+  try {
+    return await runSingleJob('generate_proxy', { path, settings });
+  } catch (error) {
+    console.error("Error generating proxy:", error);
+    throw error;
+  }
+}
+
+export async function getProxy(path: string): Promise<string | null> {
+  try {
+    return await invoke<string | null>('get_proxy', { path });
+  } catch (error) {
+    console.error("Error getting proxy:", error);
+    throw error;
+  }
+}
+
+// URL the player can switch to once a proxy exists, null until then
+export async function getProxyUrl(path: string): Promise<string | null> {
+  try {
+    return await invoke<string | null>('get_proxy_url', { path });
+  } catch (error) {
+    console.error("Error getting proxy URL:", error);
+    throw error;
+  }
+}
+
+// Returns the path of a cached image of the frame at `time` seconds
+export async function extractFrame(
+  path: string,
+  time: number,
+  width?: number,
+  format?: ImageFormat
+): Promise<string> {
+  try {
+    return await invoke<string>('extract_frame', { path, time, width, format });
+  } catch (error) {
+    console.error("Error extracting frame:", error);
+    throw error;
+  }
+}
+
+// Base URL for frames of an opened file, append `?t=<seconds>&width=<px>`
+export async function getFrameUrl(path: string): Promise<string> {
+  try {
+    return await invoke<string>('get_frame_url', { path });
+  } catch (error) {
+    console.error("Error getting frame URL:", error);
+    throw error;
+  }
+}
+
+// loadVideo already queues thumbnails with default settings. Resolves to the
+// job ID, or null when thumbnails for the file are already cached.
+export async function generateThumbnails(
+  path: string,
+  settings?: SpriteSettings
+): Promise<string | null> {
+  try {
+    return await invoke<string | null>('generate_thumbnails', { path, settings });
+  } catch (error) {
+    console.error("Error generating thumbnails:", error);
+    throw error;
+  }
+}
+
+// WebVTT index mapping times to sprite-sheet rectangles, 404 until generated
+export async function getThumbnailsUrl(path: string): Promise<string> {
+  try {
+    return await invoke<string>('get_thumbnails_url', { path });
+  } catch (error) {
+    console.error("Error getting thumbnails URL:", error);
+    throw error;
+  }
+}
+
+// Peaks for the whole file, or for start..end seconds when zoomed in.
+// streamIndex is AudioStream.index, the first audio stream when omitted.
+export async function getWaveform(
+  path: string,
+  buckets: number,
+  options: { streamIndex?: number; start?: number; end?: number } = {}
+): Promise<Waveform> {
+  try {
+    return await invoke<Waveform>('get_waveform', { path, buckets, ...options });
+  } catch (error) {
+    console.error("Error getting waveform:", error);
+    throw error;
+  }
+}
+
+export async function detectScenes(
+  path: string,
+  options: { threshold?: number; minLength?: number } = {},
+  onCut?: (cut: SceneDetected) => void
+): Promise<SceneDetection> {
+  const unlisten = onCut
+    ? await listen<SceneDetected>("scene_detected", (event) => {
+        if (event.payload.path === path) {
+          onCut(event.payload);
+        }
+      })
+    : null;
+
+  try {
+    return await invoke<SceneDetection>('detect_scenes', { path, ...options });
+  } catch (error) {
+    console.error("Error detecting scenes:", error);
+    throw error;
+  } finally {
+    unlisten?.();
+  }
+}
+
 export async function saveVideo(
   inputPath: string,
   outputPath: string
@@ -135,15 +421,15 @@ export const addIntroWithProgress = async (
     quality: number;
     preset: string;
     codec: string;
-  }
+  },
+  transition?: Transition
 ) => {
-  const appWindow = getCurrentWindow();
-  return invoke('add_intro_with_progress', {
+  return runSingleJob('add_intro_with_progress', {
     introPath,
     videoPath,
     outputDir,
     settings: compressionSettings,
-    window: appWindow
+    transition
   });
 };
 
@@ -156,24 +442,70 @@ export async function processVideo(
   onProgress: (progress: SegmentProgress) => void
 ): Promise<ProcessingResult[]> {
   try {
-    // Listen for progress updates from Rust
-    const unlisten = await listen("segment_progress", (event) => {
-      const progressData = event.payload as SegmentProgress
-      onProgress(progressData) // Call the progress callback
-    })
-
-    // Invoke the new Rust function with compression settings
-    const result = await invoke<ProcessingResult[]>("process_video_with_progress", {
+    return await runJob("process_video_with_progress", {
       inputPath,
       segments,
       outputDir,
       compressionSettings, // Passing compression settings
-    })
-
-    unlisten() // Stop listening when processing is complete
-    return result
+    }, onProgress)
   } catch (error) {
     console.error("Error processing video:", error)
     throw error
   }
 }
+
+export async function detectSilence(
+  path: string,
+  options: { noiseDb?: number; minDuration?: number } = {}
+): Promise<TimeRange[]> {
+  try {
+    return await invoke<TimeRange[]>('detect_silence', { path, ...options });
+  } catch (error) {
+    console.error("Error detecting silence:", error);
+    throw error;
+  }
+}
+
+export async function detectBlackFrames(
+  path: string,
+  options: { minDuration?: number; pixelThreshold?: number } = {}
+): Promise<TimeRange[]> {
+  try {
+    return await invoke<TimeRange[]>('detect_black_frames', { path, ...options });
+  } catch (error) {
+    console.error("Error detecting black frames:", error);
+    throw error;
+  }
+}
+
+export async function detectFrozenFrames(
+  path: string,
+  options: { noiseDb?: number; minDuration?: number } = {}
+): Promise<TimeRange[]> {
+  try {
+    return await invoke<TimeRange[]>('detect_frozen_frames', { path, ...options });
+  } catch (error) {
+    console.error("Error detecting frozen frames:", error);
+    throw error;
+  }
+}
+
+// Cuts out silent ranges and joins the rest, returns the output path
+export async function removeSilence(
+  inputPath: string,
+  outputDir: string,
+  settings?: SilenceSettings,
+  compressionSettings?: CompressionSettings
+): Promise<string> {
+  try {
+    return await runSingleJob('remove_silence', {
+      inputPath,
+      outputDir,
+      settings,
+      compressionSettings
+    });
+  } catch (error) {
+    console.error("Error removing silence:", error);
+    throw error;
+  }
+}
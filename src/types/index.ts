@@ -3,7 +3,35 @@ export interface VideoSegment {
   start_time: number;
   end_time: number;
   intro_path?: string;
+  pre_roll?: string[]; // Clips played after the intro, before the segment
+  post_roll?: string[]; // Clips played after the segment, e.g. outro and end card
+  fade_in?: Fade; // Forces an accurate cut
+  fade_out?: Fade;
+  transition?: Transition; // Between the bumpers and the segment
   output_name: string; 
+  cut_mode?: CutMode; // defaults to 'copy'
+  trim_black_edges?: boolean; // Skip black or frozen frames at either end
+}
+
+// 'copy' snaps to keyframes, 'accurate' re-encodes, 'smart' re-encodes only the ends
+export type CutMode = 'copy' | 'accurate' | 'smart';
+
+// Fade lengths in seconds, 0 or missing for none
+export interface Fade {
+  video?: number;
+  audio?: number;
+}
+
+export type TransitionKind = 'none' | 'crossfade' | 'fade_black' | 'fade_white' | 'wipe';
+
+export interface Transition {
+  kind: TransitionKind;
+  duration?: number; // Seconds the clips overlap, 1 by default
+}
+
+// A segment of `source_path` placed on the timeline
+export interface TimelineClip extends Omit<VideoSegment, 'id'> {
+  source_path: string;
 }
 
 export interface VideoMetadata {
@@ -12,12 +40,86 @@ export interface VideoMetadata {
   height: number;
   framerate: number;
   codec: string;
+  format: string;
+  bit_rate?: number;
+  video_bit_rate?: number;
+  pixel_format?: string;
+  color_space?: string;
+  color_transfer?: string; // 'smpte2084' or 'arib-std-b67' means HDR
+  color_primaries?: string;
+  rotation: number; // degrees clockwise
+  sample_aspect_ratio?: string;
+  variable_frame_rate: boolean;
+  audio_streams: AudioStream[];
+  subtitle_streams: SubtitleStream[];
+  chapters: Chapter[];
+  tags: Record<string, string>;
+}
+
+export interface AudioStream {
+  index: number;
+  codec: string;
+  channels: number;
+  channel_layout?: string;
+  sample_rate?: number;
+  bit_rate?: number;
+  language?: string;
+}
+
+export interface SubtitleStream {
+  index: number;
+  codec: string;
+  language?: string;
+  title?: string;
+}
+
+export interface Chapter {
+  start: number;
+  end: number;
+  title?: string;
+}
+
+export interface Waveform {
+  start: number; // Range the peaks cover, in seconds
+  end: number;
+  duration: number; // Of the whole audio stream
+  peaks: [number, number][]; // [min, max] per bucket, between -1 and 1
+}
+
+export type ImageFormat = 'jpeg' | 'png' | 'webp';
+
+export interface ProxySettings {
+  height: number; // Maximum height in pixels
+  keyframe_interval: number; // Frames between keyframes, 1 for all-intra
+  quality: number; // CRF, 0-51 (lower is better)
+}
+
+export interface SpriteSettings {
+  interval: number; // Seconds between thumbnails
+  scene_threshold?: number; // 0-1, sample on scene changes instead of the interval
+  width: number; // Thumbnail width in pixels
+  columns: number;
+  rows: number;
 }
 
 export interface CompressionSettings {
   quality: number; // 0-51 for x264/x265 (lower is better)
   preset: string; // e.g., "medium", "slow", "veryslow"
   codec: string; // e.g., "libx264", "libx265"
+  overlay?: OverlaySettings; // Watermark burned in while compressing
+}
+
+export type Anchor = 'top_left' | 'top_right' | 'bottom_left' | 'bottom_right' | 'center';
+
+export interface OverlaySettings {
+  image_path: string;
+  anchor?: Anchor; // 'bottom_right' by default
+  margin_x?: number; // Pixels from the anchored edge, 24 by default
+  margin_y?: number;
+  scale?: number; // Fraction of the video width, 0.15 by default
+  opacity?: number; // 0-1
+  start?: number; // Seconds, shown for the whole video if unset
+  end?: number;
 }
 
 export type PresetOption = 'ultrafast' | 'superfast' | 'veryfast' | 'faster' | 'fast' | 'medium' | 'slow' | 'slower' | 'veryslow';
@@ -28,12 +130,93 @@ export interface ProcessingResult {
   success: boolean;
   output_path?: string;
   error_message?: string;
+  error?: CommandError;
+  actual_start?: number; // Source range the cut really covers, may differ from
+  actual_end?: number;   // the requested one in 'copy' mode
 }
 
 export interface SegmentProgress {
+  job_id: string;     // ID of the job this segment belongs to
   index: number;      // Current segment index (starting from 0)
   total: number;      // Total number of segments
-  status: string;     // Status of the process ("cutting", "adding_intro", etc.)
-  progress: number;   // Progress percentage (0 to 100)
+  status: string;     // Current step ("cutting", "adding bumpers", "compressing", etc.)
+  progress: number;   // Progress percentage of the current step (0 to 100)
   estimated_time?: number; // Estimated time remaining (optional, in seconds)
-}
\ No newline at end of file
+  fps?: number;       // Frames per second FFmpeg is processing
+  speed?: number;     // Processing speed as a multiple of realtime
+}
+
+export type JobStatus = 'queued' | 'running' | 'completed' | 'failed' | 'cancelled' | 'interrupted';
+
+export type JobKind = 'cut' | 'process' | 'add_intro' | 'compress' | 'proxy' | 'thumbnails' | 'remove_silence' | 'render_timeline';
+
+export interface JobRecord {
+  id: string;
+  status: JobStatus;
+  created_at: number;  // Milliseconds since the Unix epoch
+  kind: JobKind;
+  results: (ProcessingResult | null)[]; // One entry per segment, null while pending
+  [param: string]: unknown; // Command arguments the job was started with
+}
+
+export interface JobFinished {
+  job_id: string;
+  status: JobStatus;
+  results: ProcessingResult[];
+}
+
+// Error returned by commands and carried in ProcessingResult.error
+export type CommandError =
+  | { kind: 'ffmpeg_not_found'; path: string }
+  | { kind: 'probe_failed'; path: string; exit_code?: number; stderr: string }
+  | { kind: 'ffmpeg_failed'; context: string; exit_code?: number; stderr: string }
+  | { kind: 'invalid_segment'; message: string; diagnostics: SegmentDiagnostic[] }
+  | { kind: 'encoder_unavailable'; encoder: string; exit_code?: number; stderr: string }
+  | { kind: 'disk_full'; exit_code?: number; stderr: string }
+  | { kind: 'cancelled' }
+  | { kind: 'io'; message: string }
+  | { kind: 'job_not_found'; job_id: string }
+  | { kind: 'invalid_request'; message: string }
+  | { kind: 'other'; message: string };
+
+export type SegmentIssue =
+  | { kind: 'negative_start' }
+  | { kind: 'end_beyond_duration'; duration: number }
+  | { kind: 'start_beyond_duration'; duration: number }
+  | { kind: 'empty_range' }
+  | { kind: 'overlap'; other: number }
+  | { kind: 'invalid_output_name'; reason: string };
+
+export interface SegmentDiagnostic {
+  index: number;
+  start_time: number; // Clamped to the video
+  end_time: number;
+  errors: SegmentIssue[]; // Block processing
+  warnings: SegmentIssue[]; // Fixed up automatically
+}
+
+export interface SceneCut {
+  time: number; // Seconds
+  score: number; // 0-1, how different the frame is from the previous one
+}
+
+export interface SceneDetection {
+  cuts: SceneCut[];
+  segments: Omit<VideoSegment, 'id'>[]; // One per scene, ready for addSegment
+}
+
+export interface SceneDetected extends SceneCut {
+  path: string;
+  progress: number; // 0-100
+}
+
+export interface TimeRange {
+  start: number; // Seconds
+  end: number;
+}
+
+export interface SilenceSettings {
+  noise_db?: number; // Audio below this level is silence, -30 by default
+  min_duration?: number; // Shortest silence in seconds
+  padding?: number; // Seconds of silence kept around speech
+}